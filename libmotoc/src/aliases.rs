@@ -0,0 +1,238 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::profiles::{config_dir, write_atomic};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// tracking origin names this similar after normalizing are offered as fuzzy matches
+const FUZZY_MIN_SIMILARITY: f64 = 0.75;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NameKind {
+    Origin,
+    Device,
+}
+
+impl NameKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            NameKind::Origin => "tracking origin",
+            NameKind::Device => "device",
+        }
+    }
+}
+
+/// How a saved name was found among the live ones.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Resolution<'a> {
+    Exact(&'a str),
+    Alias(&'a str),
+    /// a single live name that is similar, but not known to be the same
+    Fuzzy(&'a str),
+    Missing,
+}
+
+/// User-defined alternative names for tracking origins and device serials,
+/// kept in `aliases.json` next to the profiles.
+///
+/// Each entry maps a name to the other names it may appear under, e.g. after a
+/// driver update renamed it. Names in the same entry are treated as the same.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Aliases {
+    pub origins: BTreeMap<String, Vec<String>>,
+    pub devices: BTreeMap<String, Vec<String>>,
+}
+
+impl Aliases {
+    pub fn load() -> Result<Self> {
+        let mut path = config_dir()?;
+        path.push("aliases.json");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut path = config_dir()?;
+        path.push("aliases.json");
+        write_atomic(&path, &serde_json::to_string_pretty(self)?)
+    }
+
+    fn group(&self, kind: NameKind) -> &BTreeMap<String, Vec<String>> {
+        match kind {
+            NameKind::Origin => &self.origins,
+            NameKind::Device => &self.devices,
+        }
+    }
+
+    /// How similar a live name must be to be offered. Serials that differ in a
+    /// single character belong to different devices, so those only match if
+    /// they are equal after normalizing.
+    fn min_similarity(kind: NameKind) -> f64 {
+        match kind {
+            NameKind::Origin => FUZZY_MIN_SIMILARITY,
+            NameKind::Device => 1.0,
+        }
+    }
+
+    fn group_mut(&mut self, kind: NameKind) -> &mut BTreeMap<String, Vec<String>> {
+        match kind {
+            NameKind::Origin => &mut self.origins,
+            NameKind::Device => &mut self.devices,
+        }
+    }
+
+    /// Whether the two names are equal or aliases of each other.
+    pub fn same(&self, kind: NameKind, a: &str, b: &str) -> bool {
+        a == b
+            || self.group(kind).iter().any(|(name, aliases)| {
+                let has = |x: &str| name == x || aliases.iter().any(|alias| alias == x);
+                has(a) && has(b)
+            })
+    }
+
+    pub fn add(&mut self, kind: NameKind, name: &str, alias: &str) {
+        let aliases = self.group_mut(kind).entry(name.to_string()).or_default();
+        if !aliases.iter().any(|a| a == alias) {
+            aliases.push(alias.to_string());
+        }
+    }
+
+    /// Removes an alias from every name. Returns false if it was not there.
+    pub fn remove(&mut self, kind: NameKind, alias: &str) -> bool {
+        let group = self.group_mut(kind);
+        let mut removed = false;
+        for aliases in group.values_mut() {
+            let before = aliases.len();
+            aliases.retain(|a| a != alias);
+            removed |= aliases.len() != before;
+        }
+        group.retain(|_, aliases| !aliases.is_empty());
+        removed
+    }
+
+    /// Finds a saved name among the live ones: exactly, through an alias, or by similarity.
+    pub fn resolve<'a>(&self, kind: NameKind, wanted: &str, live: &[&'a str]) -> Resolution<'a> {
+        if let Some(name) = live.iter().find(|name| **name == wanted) {
+            return Resolution::Exact(name);
+        }
+        if let Some(name) = live.iter().find(|name| self.same(kind, wanted, name)) {
+            return Resolution::Alias(name);
+        }
+
+        let mut similar = live
+            .iter()
+            .filter(|name| similarity(wanted, name) >= Self::min_similarity(kind));
+        match (similar.next(), similar.next()) {
+            (Some(name), None) => Resolution::Fuzzy(name),
+            _ => Resolution::Missing,
+        }
+    }
+}
+
+fn normalize(name: &str) -> Vec<char> {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// 1 for names that only differ in case and punctuation, falling towards 0
+/// with the edit distance between them.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    1.0 - row[b.len()] as f64 / longest as f64
+}
+
+/// Resolves saved names against the current setup, logging how each was found.
+/// Fuzzy matches are only used when the user asked for them.
+pub struct NameMatcher {
+    pub aliases: Aliases,
+    pub fuzzy: bool,
+    // names a similar one was suggested for, so it is only suggested once
+    suggested: RefCell<BTreeSet<String>>,
+}
+
+impl NameMatcher {
+    pub fn load(fuzzy: bool) -> Self {
+        Self {
+            aliases: load_aliases(),
+            fuzzy,
+            suggested: RefCell::default(),
+        }
+    }
+
+    /// Picks up aliases added since, e.g. while `motoc watch` waits.
+    pub fn reload(&mut self) {
+        self.aliases = load_aliases();
+    }
+
+    pub fn find<'a>(&self, kind: NameKind, wanted: &str, live: &[&'a str]) -> Option<&'a str> {
+        match self.aliases.resolve(kind, wanted, live) {
+            Resolution::Exact(name) => Some(name),
+            Resolution::Alias(name) => {
+                log::info!(
+                    "Using {} \"{}\" for \"{}\" (alias).",
+                    kind.label(),
+                    name,
+                    wanted
+                );
+                Some(name)
+            }
+            Resolution::Fuzzy(name) if self.fuzzy => {
+                log::warn!(
+                    "Using {} \"{}\" for \"{}\" (similar name).",
+                    kind.label(),
+                    name,
+                    wanted
+                );
+                Some(name)
+            }
+            Resolution::Fuzzy(name) => {
+                if !self.suggested.borrow_mut().insert(wanted.to_string()) {
+                    return None;
+                }
+                log::warn!(
+                    "No {} \"{}\", but \"{}\" is similar. Pass --fuzzy to use it, or make it permanent with `motoc profile rebind`.",
+                    kind.label(),
+                    wanted,
+                    name
+                );
+                None
+            }
+            Resolution::Missing => None,
+        }
+    }
+}
+
+fn load_aliases() -> Aliases {
+    Aliases::load().unwrap_or_else(|e| {
+        log::warn!("Ignoring unreadable aliases: {}", e);
+        Aliases::default()
+    })
+}
@@ -0,0 +1,183 @@
+use nalgebra::{UnitQuaternion, Vector3};
+
+use crate::{
+    calibrator::{CalibratorStatus, OffsetMethod, StepResult},
+    common::{OffsetType, SaveInfo},
+    error::{Error, ResultExt},
+    helpers_xr::SpaceLocationConvert,
+    transformd::TransformD,
+};
+
+use super::Calibrator;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// ~2s at the main loop rate
+const CAPTURE_SAMPLES: usize = 50;
+
+/// measures the current offset between two devices once the user confirms
+/// that they are aligned, then maintains it just like `OffsetMethod`
+pub struct CaptureMethod {
+    device_a: usize,
+    device_b: usize,
+    lerp_factor: f64,
+    profile: String,
+    capturing: bool,
+    positions: Vector3<f64>,
+    rotation: Option<UnitQuaternion<f64>>,
+    num_samples: usize,
+}
+
+impl CaptureMethod {
+    pub fn new(a: usize, b: usize, lerp_factor: f64, profile: String) -> Self {
+        Self {
+            device_a: a,
+            device_b: b,
+            lerp_factor,
+            profile,
+            capturing: false,
+            positions: Vector3::zeros(),
+            rotation: None,
+            num_samples: 0,
+        }
+    }
+
+    fn restart(&mut self) {
+        self.positions = Vector3::zeros();
+        self.rotation = None;
+        self.num_samples = 0;
+    }
+}
+
+impl Calibrator for CaptureMethod {
+    fn init(&mut self, data: &mut crate::common::CalibratorData) -> Result<StepResult> {
+        log::info!(
+            "Device A: {} ({})",
+            data.devices[self.device_a].serial,
+            data.devices[self.device_a].inner.name
+        );
+        log::info!(
+            "Device B: {} ({})",
+            data.devices[self.device_b].serial,
+            data.devices[self.device_b].inner.name
+        );
+        log::info!("Line up the two devices, then confirm to capture their current offset.");
+
+        Ok(StepResult::Continue)
+    }
+
+    fn trigger(&mut self) {
+        if !self.capturing {
+            log::info!("Capturing, hold still...");
+            self.capturing = true;
+        }
+    }
+
+    fn step(
+        &mut self,
+        data: &mut crate::common::CalibratorData,
+    ) -> Result<(StepResult, Option<CalibratorStatus>)> {
+        if !self.capturing {
+            return Ok((
+                StepResult::Continue,
+                Some(CalibratorStatus::Spinner {
+                    message: String::from("Waiting for confirmation that the devices are aligned."),
+                }),
+            ));
+        }
+
+        let (a_loc, a_vel) = data.devices[self.device_a]
+            .space
+            .relate(&data.stage, data.now)
+            .context("Unable to locate device A")?;
+
+        let (b_loc, b_vel) = data.devices[self.device_b]
+            .space
+            .relate(&data.stage, data.now)
+            .context("Unable to locate device B")?;
+
+        let [Ok(pose_a), Ok(pose_b)] = [a_loc.into_transformd(), b_loc.into_transformd()] else {
+            self.restart();
+            return Ok((
+                StepResult::Continue,
+                Some(CalibratorStatus::Spinner {
+                    message: String::from("Device(s) not tracking."),
+                }),
+            ));
+        };
+
+        // same thresholds as OffsetMethod; the capture window must be still
+        if data.tuning.too_fast(&a_vel) || data.tuning.too_fast(&b_vel) {
+            self.restart();
+            return Ok((
+                StepResult::Continue,
+                Some(CalibratorStatus::Spinner {
+                    message: String::from("Device(s) moving too fast, hold still."),
+                }),
+            ));
+        }
+
+        // both poses are in STAGE, so the STAGE offset cancels out here
+        let b_to_a = pose_b.inverse() * pose_a;
+        let q = UnitQuaternion::from_rotation_matrix(&b_to_a.basis);
+
+        self.num_samples += 1;
+        self.positions += b_to_a.origin;
+        self.rotation = Some(match self.rotation {
+            Some(avg) => avg.slerp(&q, 1.0 / self.num_samples as f64),
+            None => q,
+        });
+
+        if self.num_samples < CAPTURE_SAMPLES {
+            return Ok((
+                StepResult::Continue,
+                Some(CalibratorStatus::Progress {
+                    current: self.num_samples as u64,
+                    max: CAPTURE_SAMPLES as u64,
+                    message: String::from("Capturing offset..."),
+                }),
+            ));
+        }
+
+        let offset = TransformD {
+            origin: self.positions.scale(1.0 / self.num_samples as f64),
+            basis: self
+                .rotation
+                .unwrap_or_else(UnitQuaternion::identity)
+                .to_rotation_matrix(),
+        };
+
+        log::info!("Captured B-to-A offset: {}", offset);
+
+        match data.save_calibration(
+            &self.profile,
+            self.device_a,
+            self.device_b,
+            offset,
+            OffsetType::Device,
+            SaveInfo {
+                samples: Some(self.num_samples as _),
+                ..Default::default()
+            },
+        ) {
+            Ok(_) => {
+                log::info!("Saved calibration. Use `motoc continue` on next startup to use this.")
+            }
+            Err(e) => log::warn!("Could not save calibration: {}", e),
+        }
+
+        Ok((
+            StepResult::Replace(Box::new(OffsetMethod::new_internal(
+                self.device_a,
+                self.device_b,
+                offset,
+                self.lerp_factor,
+            ))),
+            None,
+        ))
+    }
+
+    fn finish(&mut self, _data: &mut crate::common::CalibratorData) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::VecDeque;
+
+use nalgebra::{Matrix3, Rotation3, UnitQuaternion, Vector3};
+use openxr as xr;
+
+use crate::{
+    calibrator::{CalibratorStatus, SampledMethod, StepResult},
+    common::CalibratorData,
+    error::Error,
+    helpers_xr::SpaceLocationConvert,
+    transformd::TransformD,
+};
+
+use super::Calibrator;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// rotations are compared across this many frames, ~0.4s at the main loop rate
+const RIGID_WINDOW: usize = 10;
+// windows where neither device rotated this much carry no information
+const RIGID_MIN_MOTION_DEG: f64 = 5.0;
+// rigidly attached devices rotate by the same angle, give or take tracking noise
+pub(crate) const RIGID_MAX_MISMATCH_DEG: f64 = 3.0;
+// and the mount between them does not slip by more than this
+pub(crate) const RIGID_MAX_SLIP_M: f64 = 0.02;
+// the mount is fitted to this many recent motions, ~6s of moving
+const RIGID_HISTORY: usize = 150;
+// a pair needs this many moving frames before it is scored at all
+const DISCOVER_MIN_MOVING_FRAMES: usize = 25;
+
+/// how far the motion of two devices is from that of one rigid body
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Mismatch {
+    pub degrees: f64,
+    pub meters: f64,
+}
+
+impl Mismatch {
+    pub fn is_rigid(&self) -> bool {
+        self.degrees <= RIGID_MAX_MISMATCH_DEG && self.meters <= RIGID_MAX_SLIP_M
+    }
+}
+
+/// checks whether two devices move as one rigid body
+///
+/// over a short window, each device moves by a local delta. for rigidly attached
+/// devices, these deltas are the same motion seen through a constant mount `K`:
+/// `delta_a * K = K * delta_b`. this holds regardless of the offset between their
+/// tracking origins, which is what makes the check usable before calibrating.
+/// devices that only happen to turn together, e.g. with the whole body, do not
+/// keep fitting one mount once they move in other directions.
+#[derive(Default)]
+pub(crate) struct RigidPair {
+    history: VecDeque<(TransformD, TransformD)>,
+    // local deltas of recent moving windows
+    motions: VecDeque<(TransformD, TransformD)>,
+    moving_frames: usize,
+}
+
+impl RigidPair {
+    /// adds the current poses, or `None` if either device is not tracking.
+    /// returns how far the latest motion is from a rigid one, if the devices moved enough to tell.
+    pub(crate) fn push(&mut self, poses: Option<(TransformD, TransformD)>) -> Option<Mismatch> {
+        let Some(poses) = poses else {
+            self.history.clear();
+            return None;
+        };
+
+        self.history.push_back(poses);
+        if self.history.len() <= RIGID_WINDOW {
+            return None;
+        }
+        let (old_a, old_b) = self.history.pop_front()?;
+        let (new_a, new_b) = poses;
+
+        // the local delta of each device; origin offsets cancel out here
+        let delta_a = old_a.inverse() * new_a;
+        let delta_b = old_b.inverse() * new_b;
+        let angle_a = delta_a.basis.angle().to_degrees();
+        let angle_b = delta_b.basis.angle().to_degrees();
+
+        if angle_a.max(angle_b) < RIGID_MIN_MOTION_DEG {
+            return None;
+        }
+
+        self.moving_frames += 1;
+        self.motions.push_back((delta_a, delta_b));
+        if self.motions.len() > RIGID_HISTORY {
+            self.motions.pop_front();
+        }
+        let mount = fit_mount(&self.motions);
+        Some(motion_mismatch(&mount, &delta_a, &delta_b))
+    }
+
+    pub(crate) fn moving_frames(&self) -> usize {
+        self.moving_frames
+    }
+
+    /// mean mismatch of the recent motions against the mount that fits them best
+    pub(crate) fn mean_mismatch(&self) -> Option<Mismatch> {
+        if self.motions.is_empty() {
+            return None;
+        }
+        let mount = fit_mount(&self.motions);
+        let mut sum = Mismatch::default();
+        for (delta_a, delta_b) in self.motions.iter() {
+            let m = motion_mismatch(&mount, delta_a, delta_b);
+            sum.degrees += m.degrees;
+            sum.meters += m.meters;
+        }
+        let n = self.motions.len() as f64;
+        Some(Mismatch {
+            degrees: sum.degrees / n,
+            meters: sum.meters / n,
+        })
+    }
+}
+
+/// the mount `K` with `delta_a * K = K * delta_b` for all motions, in the least squares sense.
+/// directions the motions do not constrain are left at zero.
+fn fit_mount(motions: &VecDeque<(TransformD, TransformD)>) -> TransformD {
+    // rotation axes relate by the mount: axis_a = K * axis_b (Wahba's problem)
+    let mut cov = Matrix3::zeros();
+    for (delta_a, delta_b) in motions.iter() {
+        cov += delta_a.basis.scaled_axis() * delta_b.basis.scaled_axis().transpose();
+    }
+    let svd = cov.svd(true, true);
+    let basis = match (svd.u, svd.v_t) {
+        (Some(u), Some(v_t)) => {
+            let flip = Matrix3::from_diagonal(&Vector3::new(1.0, 1.0, (u * v_t).determinant()));
+            Rotation3::from_matrix_unchecked(u * flip * v_t)
+        }
+        _ => Rotation3::identity(),
+    };
+
+    // then (rot_a - I) * t = K * t_b - t_a
+    let mut ata = Matrix3::zeros();
+    let mut atb = Vector3::zeros();
+    for (delta_a, delta_b) in motions.iter() {
+        let a = delta_a.basis.matrix() - Matrix3::identity();
+        let b = basis * delta_b.origin - delta_a.origin;
+        ata += a.transpose() * a;
+        atb += a.transpose() * b;
+    }
+    let origin = ata
+        .pseudo_inverse(1e-9)
+        .map(|inv| inv * atb)
+        .unwrap_or_else(|_| Vector3::zeros());
+
+    TransformD { origin, basis }
+}
+
+fn motion_mismatch(mount: &TransformD, delta_a: &TransformD, delta_b: &TransformD) -> Mismatch {
+    let residual = (*delta_a * *mount).inverse() * (*mount * *delta_b);
+    Mismatch {
+        // `Rotation3::angle` turns NaN for rounding errors near the identity
+        degrees: UnitQuaternion::from_rotation_matrix(&residual.basis)
+            .angle()
+            .to_degrees(),
+        meters: residual.origin.norm(),
+    }
+}
+
+/// a candidate pair of devices from different tracking origins
+#[derive(Clone, Debug)]
+pub struct PairScore {
+    pub src_dev: usize,
+    pub dst_dev: usize,
+    /// mean mismatch against one rigid mount, lower is more rigid
+    pub mismatch: Mismatch,
+    pub moving_frames: usize,
+}
+
+impl PairScore {
+    pub fn is_rigid(&self) -> bool {
+        self.moving_frames >= DISCOVER_MIN_MOVING_FRAMES && self.mismatch.is_rigid()
+    }
+}
+
+/// watches every cross-origin device pair to find the ones that are physically attached
+pub struct PairDiscovery {
+    pairs: Vec<(usize, usize, RigidPair)>,
+    start: Option<xr::Time>,
+    duration_ns: i64,
+}
+
+impl PairDiscovery {
+    pub fn new(data: &CalibratorData, seconds: f64) -> Self {
+        // prefer the device in the HMD's origin as the source, as in `motoc calibrate`
+        let head_origin = data.head_tracking_origin().ok();
+
+        let mut pairs = Vec::new();
+        for a in 0..data.devices.len() {
+            for b in (a + 1)..data.devices.len() {
+                let (origin_a, origin_b) = (
+                    data.devices[a].tracking_origin,
+                    data.devices[b].tracking_origin,
+                );
+                if origin_a == origin_b {
+                    continue;
+                }
+                if Some(origin_b) == head_origin {
+                    pairs.push((b, a, RigidPair::default()));
+                } else {
+                    pairs.push((a, b, RigidPair::default()));
+                }
+            }
+        }
+
+        Self {
+            pairs,
+            start: None,
+            duration_ns: (seconds * 1e9) as i64,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// feeds the current poses of all devices; returns true once the time is up
+    pub fn update(&mut self, data: &CalibratorData) -> bool {
+        let start = *self.start.get_or_insert(data.now);
+
+        let poses: Vec<Option<TransformD>> = data
+            .devices
+            .iter()
+            .map(|dev| {
+                dev.space
+                    .locate(&data.stage, data.now)
+                    .ok()
+                    .and_then(|loc| loc.into_transformd().ok())
+            })
+            .collect();
+
+        for (a, b, pair) in self.pairs.iter_mut() {
+            let (Some(Some(pose_a)), Some(Some(pose_b))) = (poses.get(*a), poses.get(*b)) else {
+                pair.push(None);
+                continue;
+            };
+            pair.push(Some((*pose_a, *pose_b)));
+        }
+
+        data.now.as_nanos() - start.as_nanos() >= self.duration_ns
+    }
+
+    pub fn progress(&self, data: &CalibratorData) -> f64 {
+        let Some(start) = self.start else {
+            return 0.0;
+        };
+        ((data.now.as_nanos() - start.as_nanos()) as f64 / self.duration_ns as f64).clamp(0.0, 1.0)
+    }
+
+    /// all pairs that moved enough to be scored, most rigid first
+    pub fn ranked(&self) -> Vec<PairScore> {
+        let mut scores: Vec<PairScore> = self
+            .pairs
+            .iter()
+            .filter_map(|(a, b, pair)| {
+                Some(PairScore {
+                    src_dev: *a,
+                    dst_dev: *b,
+                    mismatch: pair.mean_mismatch()?,
+                    moving_frames: pair.moving_frames(),
+                })
+            })
+            .collect();
+        scores.sort_by(|x, y| x.mismatch.degrees.total_cmp(&y.mismatch.degrees));
+        scores
+    }
+
+    /// the most rigid pair, if any of them is rigid at all
+    pub fn best(&self) -> Option<PairScore> {
+        self.ranked().into_iter().find(PairScore::is_rigid)
+    }
+}
+
+/// finds the physically attached device pair, then optionally calibrates it
+pub struct DiscoverMethod {
+    seconds: f64,
+    discovery: Option<PairDiscovery>,
+    calibrate: Option<(bool, u32, String)>,
+}
+
+impl DiscoverMethod {
+    pub fn new(seconds: f64) -> Self {
+        Self {
+            seconds,
+            discovery: None,
+            calibrate: None,
+        }
+    }
+
+    /// Starts a `SampledMethod` on the detected pair instead of only reporting it.
+    pub fn then_calibrate(mut self, maintain: bool, samples: u32, profile: String) -> Self {
+        self.calibrate = Some((maintain, samples, profile));
+        self
+    }
+}
+
+impl Calibrator for DiscoverMethod {
+    fn init(&mut self, data: &mut CalibratorData) -> Result<StepResult> {
+        let discovery = PairDiscovery::new(data, self.seconds);
+        if discovery.is_empty() {
+            return Err(Error::NothingToCompare);
+        }
+        self.discovery = Some(discovery);
+
+        log::info!(
+            "Move the attached devices around for {:.0} seconds, rotating them in all directions.",
+            self.seconds
+        );
+
+        Ok(StepResult::Continue)
+    }
+
+    fn step(
+        &mut self,
+        data: &mut CalibratorData,
+    ) -> Result<(StepResult, Option<CalibratorStatus>)> {
+        let Some(discovery) = self.discovery.as_mut() else {
+            return Ok((StepResult::End, None));
+        };
+
+        if !discovery.update(data) {
+            return Ok((
+                StepResult::Continue,
+                Some(CalibratorStatus::Progress {
+                    current: (discovery.progress(data) * 100.0) as u64,
+                    max: 100,
+                    message: String::from("Looking for attached devices..."),
+                }),
+            ));
+        }
+
+        for score in discovery.ranked().iter().take(5) {
+            log::info!(
+                "{} + {}: {:.1}° / {:.1} cm mismatch over {} frames",
+                data.devices[score.src_dev].serial,
+                data.devices[score.dst_dev].serial,
+                score.mismatch.degrees,
+                score.mismatch.meters * 100.0,
+                score.moving_frames
+            );
+        }
+
+        let Some(best) = discovery.best() else {
+            log::warn!("No attached pair found. Move the devices more, or pick them by hand.");
+            return Ok((StepResult::End, None));
+        };
+
+        let (src, dst) = (&data.devices[best.src_dev], &data.devices[best.dst_dev]);
+        log::info!("Attached pair: {} + {}", src.serial, dst.serial);
+
+        let Some((maintain, samples, profile)) = self.calibrate.take() else {
+            log::info!(
+                "Calibrate with: motoc calibrate --src \"{}\" --dst \"{}\"",
+                src.serial,
+                dst.serial
+            );
+            return Ok((StepResult::End, None));
+        };
+
+        Ok((
+            StepResult::Replace(Box::new(SampledMethod::new(
+                best.src_dev,
+                best.dst_dev,
+                maintain,
+                samples,
+                profile,
+            ))),
+            None,
+        ))
+    }
+
+    fn finish(&mut self, _data: &mut CalibratorData) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,9 +1,10 @@
-use std::{mem::MaybeUninit, ptr};
-
 use libmonado as mnd;
 use openxr as xr;
 
 use crate::error::{Error, ResultExt};
+use crate::helpers_xr::locate_hand_joints;
+use crate::journal::{ContinuousChange, OffsetTarget};
+use crate::lock::{lock_offset, OffsetLock};
 
 use super::{Calibrator, CalibratorStatus, StepResult};
 
@@ -13,6 +14,8 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub struct FloorMethod {
     hands: Vec<xr::HandTracker>,
     ext_hand_tracking: xr::raw::HandTrackingEXT,
+    change: ContinuousChange,
+    lock: Option<OffsetLock>,
 }
 
 impl FloorMethod {
@@ -33,12 +36,15 @@ impl FloorMethod {
         Ok(Self {
             hands,
             ext_hand_tracking,
+            change: ContinuousChange::new("floor"),
+            lock: None,
         })
     }
 }
 
 impl Calibrator for FloorMethod {
     fn init(&mut self, _data: &mut crate::common::CalibratorData) -> Result<StepResult> {
+        self.lock = Some(lock_offset(&OffsetTarget::Stage, "floor")?);
         Ok(StepResult::Continue)
     }
 
@@ -48,44 +54,21 @@ impl Calibrator for FloorMethod {
     ) -> Result<(StepResult, Option<CalibratorStatus>)> {
         let mut lowest_y = f32::MAX;
         for hand in self.hands.iter() {
-            unsafe {
-                let mut locations: [xr::sys::HandJointLocationEXT; xr::HAND_JOINT_COUNT] =
-                    MaybeUninit::zeroed().assume_init();
-
-                let info = xr::sys::HandJointsLocateInfoEXT {
-                    ty: xr::StructureType::HAND_JOINTS_LOCATE_INFO_EXT,
-                    next: ptr::null(),
-                    base_space: data.stage.as_raw(),
-                    time: data.now,
-                };
-
-                let mut result = xr::sys::HandJointLocationsEXT {
-                    ty: xr::StructureType::HAND_JOINT_LOCATIONS_EXT,
-                    next: ptr::null_mut(),
-                    is_active: xr::sys::Bool32::from_raw(0),
-                    joint_count: xr::HAND_JOINT_COUNT as _,
-                    joint_locations: locations.as_mut_ptr(),
-                };
-
-                let res =
-                    (self.ext_hand_tracking.locate_hand_joints)(hand.as_raw(), &info, &mut result);
-
-                if res != xr::sys::Result::SUCCESS {
-                    return Err(Error::HandJointLocation(res));
-                }
-
-                let loc: &xr::HandJointLocationEXT =
-                    &locations[xr::HandJointEXT::PALM.into_raw() as usize];
-                if !loc.location_flags.contains(
-                    xr::SpaceLocationFlags::POSITION_VALID
-                        | xr::SpaceLocationFlags::POSITION_TRACKED,
-                ) {
-                    continue;
-                }
-
-                let low_y = loc.pose.position.y - loc.radius;
-                lowest_y = lowest_y.min(low_y);
+            let Some(locations) =
+                locate_hand_joints(&self.ext_hand_tracking, hand, &data.stage, data.now)?
+            else {
+                continue;
+            };
+
+            let loc = &locations[xr::HandJointEXT::PALM.into_raw() as usize];
+            if !loc.location_flags.contains(
+                xr::SpaceLocationFlags::POSITION_VALID | xr::SpaceLocationFlags::POSITION_TRACKED,
+            ) {
+                continue;
             }
+
+            let low_y = loc.pose.position.y - loc.radius;
+            lowest_y = lowest_y.min(low_y);
         }
 
         let status = if lowest_y < 100.0 {
@@ -104,6 +87,7 @@ impl Calibrator for FloorMethod {
                 .get_reference_space_offset(mnd::ReferenceSpaceType::Stage)
                 .context("Unable to get reference offset")?;
 
+            self.change.before_write(OffsetTarget::Stage, stage.into());
             stage.position.y += lowest_y;
             data.monado
                 .set_reference_space_offset(mnd::ReferenceSpaceType::Stage, stage)
@@ -112,7 +96,9 @@ impl Calibrator for FloorMethod {
 
         Ok((StepResult::Continue, Some(status)))
     }
-    fn finish(&mut self, _data: &mut crate::common::CalibratorData) -> Result<()> {
+    fn finish(&mut self, data: &mut crate::common::CalibratorData) -> Result<()> {
+        self.change.finish(data.monado);
+        self.lock = None;
         Ok(())
     }
 }
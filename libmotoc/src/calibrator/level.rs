@@ -0,0 +1,248 @@
+use nalgebra::{Rotation3, UnitQuaternion, Vector3};
+
+use libmonado as mnd;
+
+use crate::{
+    calibrator::{CalibratorStatus, StepResult},
+    common::{OffsetType, SaveInfo, UNIT},
+    error::{Error, ResultExt},
+    helpers_xr::SpaceLocationConvert,
+    journal,
+    transformd::TransformD,
+};
+
+use super::Calibrator;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// number of frames averaged into a single captured orientation
+const CAPTURE_FRAMES: usize = 10;
+// turns smaller than this give a poor estimate of the axis
+const MIN_TURN_DEG: f64 = 20.0;
+// more than this is more likely a device that was not lying flat than a tilted floor
+const MAX_TILT_DEG: f64 = 15.0;
+
+/// levels a tilted tracking origin against the gravity-aligned STAGE
+///
+/// the user places a device from the tilted origin flat on the floor and turns it
+/// a few times, confirming each placement. the axis of those turns is the floor
+/// normal as seen by the tilted origin, which should line up with STAGE up.
+/// only pitch and roll of the origin offset are corrected.
+pub struct LevelMethod {
+    device: usize,
+    num_captures: usize,
+    profile: Option<String>,
+    captures: Vec<UnitQuaternion<f64>>,
+    capture: Option<Vec<UnitQuaternion<f64>>>,
+}
+
+impl LevelMethod {
+    pub fn new(device: usize, captures: u32, profile: Option<String>) -> Self {
+        Self {
+            device,
+            num_captures: captures.max(2) as _,
+            profile,
+            captures: Vec::new(),
+            capture: None,
+        }
+    }
+
+    fn prompt(&self) -> String {
+        if self.captures.is_empty() {
+            format!(
+                "Placement 1/{}: lay the device flat on the floor, then confirm.",
+                self.num_captures
+            )
+        } else {
+            format!(
+                "Placement {}/{}: turn the device by about 90° on the floor, then confirm.",
+                self.captures.len() + 1,
+                self.num_captures
+            )
+        }
+    }
+}
+
+fn locate_rotation(
+    data: &crate::common::CalibratorData,
+    dev: usize,
+) -> Result<UnitQuaternion<f64>> {
+    let pose = data.devices[dev]
+        .space
+        .locate(&data.stage, data.now)
+        .context("Unable to locate device in STAGE")?
+        .into_transformd()?;
+
+    let stage = TransformD::from(
+        data.monado
+            .get_reference_space_offset(mnd::ReferenceSpaceType::Stage)
+            .context("Unable to get STAGE reference")?,
+    );
+
+    Ok(UnitQuaternion::from_rotation_matrix(&(stage * pose).basis))
+}
+
+/// the common axis of the turns between the given orientations, pointing up
+///
+/// returns `None` if none of the turns were large enough.
+pub fn turn_axis(rotations: &[UnitQuaternion<f64>]) -> Option<Vector3<f64>> {
+    let mut sum = Vector3::zeros();
+
+    for (i, new) in rotations.iter().enumerate() {
+        for old in rotations[..i].iter() {
+            let Some((axis, angle)) = (new * old.inverse()).axis_angle() else {
+                continue;
+            };
+            if angle.to_degrees() < MIN_TURN_DEG {
+                continue;
+            }
+            // turning back and forth gives opposite axes
+            if axis.y < 0.0 {
+                sum -= axis.into_inner();
+            } else {
+                sum += axis.into_inner();
+            }
+        }
+    }
+
+    sum.try_normalize(f64::EPSILON)
+}
+
+impl Calibrator for LevelMethod {
+    fn init(&mut self, data: &mut crate::common::CalibratorData) -> Result<StepResult> {
+        let dev = &data.devices[self.device];
+        if data.head_tracking_origin().ok() == Some(dev.tracking_origin) {
+            return Err(Error::AlreadyLevel);
+        }
+
+        log::info!("Device: {} ({})", dev.serial, dev.inner.name);
+        log::info!("{}", self.prompt());
+
+        Ok(StepResult::Continue)
+    }
+
+    fn trigger(&mut self) {
+        if self.capture.is_none() {
+            self.capture = Some(Vec::with_capacity(CAPTURE_FRAMES));
+        }
+    }
+
+    fn step(
+        &mut self,
+        data: &mut crate::common::CalibratorData,
+    ) -> Result<(StepResult, Option<CalibratorStatus>)> {
+        if let Some(capture) = self.capture.as_mut() {
+            // untracked frames are skipped rather than averaged in
+            if let Ok(rot) = locate_rotation(data, self.device) {
+                capture.push(rot);
+            }
+
+            if capture.len() < CAPTURE_FRAMES {
+                return Ok((
+                    StepResult::Continue,
+                    Some(CalibratorStatus::Spinner {
+                        message: String::from("Capturing, hold still..."),
+                    }),
+                ));
+            }
+
+            let mut avg = capture[0];
+            for (i, q) in capture.iter().enumerate().skip(1) {
+                avg = avg.slerp(q, 1.0 / (i + 1) as f64);
+            }
+            self.capture = None;
+            self.captures.push(avg);
+
+            if self.captures.len() < self.num_captures {
+                log::info!("{}", self.prompt());
+            }
+        }
+
+        if self.captures.len() < self.num_captures {
+            return Ok((
+                StepResult::Continue,
+                Some(CalibratorStatus::Progress {
+                    current: self.captures.len() as u64,
+                    max: self.num_captures as u64,
+                    message: self.prompt(),
+                }),
+            ));
+        }
+
+        let Some(up) = turn_axis(&self.captures) else {
+            log::info!("The device was not turned enough, please start over.");
+            self.captures.clear();
+            log::info!("{}", self.prompt());
+            return Ok((StepResult::Continue, None));
+        };
+
+        // the shortest rotation has a horizontal axis, so it leaves yaw alone
+        let tilt = Rotation3::rotation_between(&up, &UNIT.Y).unwrap_or_else(Rotation3::identity);
+        let tilt_deg = tilt.angle().to_degrees();
+
+        if tilt_deg > MAX_TILT_DEG {
+            log::error!(
+                "Measured a tilt of {:.1}°, which is too much. Was the device lying flat?",
+                tilt_deg
+            );
+            return Ok((StepResult::End, None));
+        }
+
+        log::info!(
+            "Removed a tilt of {:.2}° (pitch {:.2}°, roll {:.2}°)",
+            tilt_deg,
+            up.z.atan2(up.y).to_degrees(),
+            (-up.x).atan2(up.y).to_degrees()
+        );
+
+        let origin = data
+            .get_device_origin(self.device)
+            .context("Unable to get device origin")?;
+        let root = TransformD::from(origin.get_offset().context("Unable to get origin offset")?);
+        let leveled = TransformD {
+            origin: root.origin,
+            basis: tilt * root.basis,
+        };
+        journal::set_origin_offset(&origin, leveled, "level")
+            .context("Unable to set origin offset")?;
+
+        let Some(profile) = self.profile.as_ref() else {
+            return Ok((StepResult::End, None));
+        };
+
+        let head_origin = data.head_tracking_origin()?;
+        let Some(head_root) = data.tracking_origins.get(head_origin as usize) else {
+            return Err(Error::TrackingOriginNotFound {
+                tracking_origin: head_origin,
+            });
+        };
+        let head_root = TransformD::from(
+            head_root
+                .get_offset()
+                .context("Unable to get HMD origin offset")?,
+        );
+        match data.save_calibration(
+            profile,
+            head_origin as _,
+            origin.id as _,
+            leveled * head_root.inverse(),
+            OffsetType::TrackingOrigin,
+            SaveInfo {
+                dst_device: Some(self.device),
+                samples: Some(self.captures.len() as _),
+                ..Default::default()
+            },
+        ) {
+            Ok(_) => {
+                log::info!("Saved calibration. Use `motoc continue` on next startup to use this.")
+            }
+            Err(e) => log::warn!("Could not save calibration: {}", e),
+        }
+
+        Ok((StepResult::End, None))
+    }
+
+    fn finish(&mut self, _data: &mut crate::common::CalibratorData) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,14 +1,23 @@
+mod capture;
+mod discover;
 mod floor;
+mod level;
 mod offset;
+mod points;
 mod recenter;
 mod sampled;
 
+pub use capture::CaptureMethod;
+pub(crate) use discover::RigidPair;
+pub use discover::{DiscoverMethod, Mismatch, PairDiscovery, PairScore};
 pub use floor::FloorMethod;
+pub use level::{turn_axis, LevelMethod};
 pub use offset::OffsetMethod;
+pub use points::{solve_point_pairs, PointsMethod};
 pub use recenter::RecenterMethod;
 pub use sampled::SampledMethod;
 
-use crate::common::CalibratorData;
+use crate::common::{CalibratorData, DeviceChanges};
 use crate::error::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -38,5 +47,9 @@ pub trait Calibrator {
     fn init(&mut self, data: &mut CalibratorData) -> Result<StepResult>;
     fn step(&mut self, data: &mut CalibratorData)
         -> Result<(StepResult, Option<CalibratorStatus>)>;
+    // the user confirmed the current step, e.g. by pressing Enter
+    fn trigger(&mut self) {}
+    // devices came or went, see `CalibratorData::refresh`. indices stay valid
+    fn devices_changed(&mut self, _data: &mut CalibratorData, _changes: &DeviceChanges) {}
     fn finish(&mut self, data: &mut CalibratorData) -> Result<()>;
 }
@@ -1,10 +1,14 @@
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
 use nalgebra::{Rotation3, Vector3};
 
 use crate::{
+    common::{CalibratorData, DeviceChanges},
     error::{Error, ResultExt},
-    helpers_xr::{EffectiveSpaceVelocity, SpaceLocationConvert},
+    helpers_xr::SpaceLocationConvert,
+    journal::{ContinuousChange, OffsetTarget},
+    lock::{lock_offset, OffsetLock},
+    telemetry::Deviation,
     transformd::TransformD,
 };
 
@@ -23,6 +27,8 @@ pub struct OffsetMethod {
     lerp_override_frames: u32,
     anomaly_start: Option<Instant>,
     last_pos_a: Vector3<f64>,
+    change: ContinuousChange,
+    lock: Option<OffsetLock>,
 }
 
 impl OffsetMethod {
@@ -35,6 +41,8 @@ impl OffsetMethod {
             lerp_override_frames: 0,
             anomaly_start: None,
             last_pos_a: Vector3::from_element(-1_000_000f64),
+            change: ContinuousChange::new("offset"),
+            lock: None,
         }
     }
     pub fn new(
@@ -61,6 +69,8 @@ impl OffsetMethod {
             lerp_override_frames: 0,
             anomaly_start: None,
             last_pos_a: Vector3::from_element(-1_000_000f64),
+            change: ContinuousChange::new("offset"),
+            lock: None,
         }
     }
 }
@@ -80,6 +90,15 @@ impl Calibrator for OffsetMethod {
 
         log::info!("B-to-A offset: {}", self.target_offset);
 
+        let to_b = data
+            .get_device_origin(self.device_b)
+            .context("Unable to get device B origin")?;
+        self.lock = Some(lock_offset(
+            &OffsetTarget::TrackingOrigin(to_b.name.clone()),
+            "offset",
+        )?);
+        data.telemetry.continuous = true;
+
         Ok(StepResult::Continue)
     }
 
@@ -87,6 +106,15 @@ impl Calibrator for OffsetMethod {
         &mut self,
         data: &mut crate::common::CalibratorData,
     ) -> Result<(StepResult, Option<CalibratorStatus>)> {
+        if !data.devices[self.device_a].connected || !data.devices[self.device_b].connected {
+            return Ok((
+                StepResult::Continue,
+                Some(CalibratorStatus::Spinner {
+                    message: String::from("Device(s) disconnected."),
+                }),
+            ));
+        }
+
         let (a_loc, a_vel) = data.devices[self.device_a]
             .space
             .relate(&data.stage, data.now)
@@ -106,12 +134,7 @@ impl Calibrator for OffsetMethod {
             ));
         };
 
-        // 0.25 m/s or 16 deg/s
-        if a_vel.effective_linear().norm_squared() > 0.5
-            || b_vel.effective_linear().norm_squared() > 0.5
-            || a_vel.effective_angular().norm_squared() > 0.4
-            || b_vel.effective_angular().norm_squared() > 0.4
-        {
+        if data.tuning.too_fast(&a_vel) || data.tuning.too_fast(&b_vel) {
             return Ok((
                 StepResult::Continue,
                 Some(CalibratorStatus::Spinner {
@@ -130,30 +153,36 @@ impl Calibrator for OffsetMethod {
         let target_a = pose_b * self.target_offset;
 
         let delta_global = pose_a * target_a.inverse();
+        data.telemetry.deviation = Some(Deviation::of(&delta_global));
 
         let to_b = data
             .get_device_origin(self.device_b)
             .context("Unable to get device B origin")?;
         let root_b = TransformD::from(to_b.get_offset()?);
+        self.change
+            .before_write(OffsetTarget::TrackingOrigin(to_b.name.clone()), root_b);
 
         let pos_offset = root_b.origin + delta_global.origin;
 
-        // devices are more than 100m from center → anomaly
-        if pos_offset.norm_squared() > 10000.0 {
-            // anomaly doesn't disappear within 5s → reset offset
+        // devices are too far from center → anomaly
+        if pos_offset.norm() > data.tuning.anomaly_distance {
+            // anomaly doesn't disappear in time → reset offset
             match self.anomaly_start {
                 Some(time) => {
-                    if time.elapsed() > Duration::from_secs(5) {
+                    if time.elapsed() > data.tuning.anomaly_reset() {
                         log::info!("Tracking anomaly detected. Restarting from scratch.");
                         to_b.set_offset(TransformD::default().into())
                             .context("Unable to set tracking origin B offset")?;
                         self.anomaly_start = Some(Instant::now());
+                        data.telemetry.anomaly_resets += 1;
                     }
                 }
                 None => {
                     self.anomaly_start = Some(Instant::now());
+                    data.telemetry.anomalies += 1;
                 }
             }
+            data.telemetry.anomaly = true;
 
             return Ok((
                 StepResult::Continue,
@@ -163,6 +192,7 @@ impl Calibrator for OffsetMethod {
             ));
         } else {
             self.anomaly_start = None;
+            data.telemetry.anomaly = false;
         }
 
         let message = format!(
@@ -191,13 +221,32 @@ impl Calibrator for OffsetMethod {
 
         to_b.set_offset(offset.into())
             .context("Unable to set tracking origin B offset")?;
+        data.telemetry.correction(&root_b, &offset);
 
         Ok((
             StepResult::Continue,
             Some(CalibratorStatus::Spinner { message }),
         ))
     }
-    fn finish(&mut self, _data: &mut crate::common::CalibratorData) -> Result<()> {
+    fn devices_changed(&mut self, data: &mut CalibratorData, changes: &DeviceChanges) {
+        for (label, device) in [("A", self.device_a), ("B", self.device_b)] {
+            let serial = &data.devices[device].serial;
+            if changes.removed.contains(&device) {
+                log::warn!("Device {} ({}) disconnected, pausing.", label, serial);
+            } else if changes.added.contains(&device) {
+                log::info!("Device {} ({}) is back, resuming.", label, serial);
+            }
+        }
+        // an anomaly that started before the gap says nothing about now
+        self.anomaly_start = None;
+        data.telemetry.anomaly = false;
+    }
+    fn finish(&mut self, data: &mut crate::common::CalibratorData) -> Result<()> {
+        self.change.finish(data.monado);
+        self.lock = None;
+        data.telemetry.deviation = None;
+        data.telemetry.continuous = false;
+        data.telemetry.anomaly = false;
         Ok(())
     }
 }
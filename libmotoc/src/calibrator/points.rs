@@ -0,0 +1,273 @@
+use nalgebra::{Matrix3, Rotation3, Vector3};
+
+use libmonado as mnd;
+
+use crate::{
+    calibrator::{CalibratorStatus, StepResult},
+    common::{OffsetType, SaveInfo},
+    error::{Error, ResultExt},
+    helpers_xr::SpaceLocationConvert,
+    journal,
+    transformd::TransformD,
+};
+
+use super::Calibrator;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// number of frames averaged into a single captured point
+const CAPTURE_FRAMES: usize = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Src,
+    Dst,
+}
+
+/// finds the offset from physical points touched by a device of each origin
+///
+/// the user touches the same spot with SRC_DEV, then with DST_DEV and confirms
+/// each touch; the origin offset is then solved via Kabsch point registration.
+pub struct PointsMethod {
+    src_dev: usize,
+    dst_dev: usize,
+    num_points: usize,
+    profile: String,
+    src_points: Vec<Vector3<f64>>,
+    dst_points: Vec<Vector3<f64>>,
+    capture: Option<Vec<Vector3<f64>>>,
+}
+
+impl PointsMethod {
+    pub fn new(src_dev: usize, dst_dev: usize, points: u32, profile: String) -> Self {
+        Self {
+            src_dev,
+            dst_dev,
+            num_points: points.max(3) as _,
+            profile,
+            src_points: Vec::new(),
+            dst_points: Vec::new(),
+            capture: None,
+        }
+    }
+
+    fn next_side(&self) -> Side {
+        if self.src_points.len() > self.dst_points.len() {
+            Side::Dst
+        } else {
+            Side::Src
+        }
+    }
+
+    fn prompt(&self, data: &crate::common::CalibratorData) -> String {
+        let (dev, label) = match self.next_side() {
+            Side::Src => (self.src_dev, "SRC"),
+            Side::Dst => (self.dst_dev, "DST"),
+        };
+        format!(
+            "Point {}/{}: touch the spot with {} ({}), then confirm.",
+            self.dst_points.len() + 1,
+            self.num_points,
+            label,
+            data.devices[dev].serial
+        )
+    }
+}
+
+fn locate_in_stage(data: &crate::common::CalibratorData, dev: usize) -> Result<Vector3<f64>> {
+    let pose = data.devices[dev]
+        .space
+        .locate(&data.stage, data.now)
+        .context("Unable to locate device in STAGE")?
+        .into_transformd()?;
+
+    let stage = TransformD::from(
+        data.monado
+            .get_reference_space_offset(mnd::ReferenceSpaceType::Stage)
+            .context("Unable to get STAGE reference")?,
+    );
+
+    Ok((stage * pose).origin)
+}
+
+/// solves for the rigid transform that maps `dst` points onto `src` points
+///
+/// returns `None` if the points are too close to collinear to define a rotation.
+pub fn solve_point_pairs(src: &[Vector3<f64>], dst: &[Vector3<f64>]) -> Option<TransformD> {
+    if src.len() != dst.len() || src.len() < 3 {
+        return None;
+    }
+
+    let len_recip = 1.0 / src.len() as f64;
+    let src_centroid = src.iter().sum::<Vector3<f64>>() * len_recip;
+    let dst_centroid = dst.iter().sum::<Vector3<f64>>() * len_recip;
+
+    let mut cross_cv = Matrix3::zeros();
+    for (a, b) in src.iter().zip(dst.iter()) {
+        cross_cv += (b - dst_centroid) * (a - src_centroid).transpose();
+    }
+
+    let svd = cross_cv.svd(true, true);
+
+    // a second singular value near zero means all points lie on a line
+    let mut singular = svd.singular_values;
+    singular.as_mut_slice().sort_by(|a, b| b.total_cmp(a));
+    if singular[1] < 1e-4 {
+        return None;
+    }
+
+    let u = svd.u?;
+    let v = svd.v_t?.transpose();
+
+    let mut i = Matrix3::identity();
+    if (v * u.transpose()).determinant() < 0.0 {
+        i.row_mut(2)[2] = -1.0;
+    }
+
+    let basis = Rotation3::from_matrix_unchecked(v * i * u.transpose());
+
+    Some(TransformD {
+        origin: src_centroid - basis * dst_centroid,
+        basis,
+    })
+}
+
+// root-mean-square distance between `src` points and the transformed `dst` points
+fn point_pairs_rms(src: &[Vector3<f64>], dst: &[Vector3<f64>], offset: &TransformD) -> f64 {
+    let sum = src
+        .iter()
+        .zip(dst.iter())
+        .map(|(a, b)| (a - (offset.origin + offset.basis * b)).norm_squared())
+        .sum::<f64>();
+    (sum / src.len().max(1) as f64).sqrt()
+}
+
+impl Calibrator for PointsMethod {
+    fn init(&mut self, data: &mut crate::common::CalibratorData) -> Result<StepResult> {
+        log::info!(
+            "Touch the same {} physical spots with both devices, confirming each touch.",
+            self.num_points
+        );
+        log::info!("Spread the spots out; they must not lie on a single line.");
+        log::info!("{}", self.prompt(data));
+
+        Ok(StepResult::Continue)
+    }
+
+    fn trigger(&mut self) {
+        if self.capture.is_none() {
+            self.capture = Some(Vec::with_capacity(CAPTURE_FRAMES));
+        }
+    }
+
+    fn step(
+        &mut self,
+        data: &mut crate::common::CalibratorData,
+    ) -> Result<(StepResult, Option<CalibratorStatus>)> {
+        let side = self.next_side();
+
+        if let Some(capture) = self.capture.as_mut() {
+            let dev = match side {
+                Side::Src => self.src_dev,
+                Side::Dst => self.dst_dev,
+            };
+
+            // untracked frames are skipped rather than averaged in
+            if let Ok(pos) = locate_in_stage(data, dev) {
+                capture.push(pos);
+            }
+
+            if capture.len() < CAPTURE_FRAMES {
+                return Ok((
+                    StepResult::Continue,
+                    Some(CalibratorStatus::Spinner {
+                        message: String::from("Capturing, hold still..."),
+                    }),
+                ));
+            }
+
+            let point = capture.iter().sum::<Vector3<f64>>() / capture.len() as f64;
+            self.capture = None;
+
+            match side {
+                Side::Src => self.src_points.push(point),
+                Side::Dst => self.dst_points.push(point),
+            }
+
+            if self.dst_points.len() < self.num_points {
+                log::info!("{}", self.prompt(data));
+            }
+        }
+
+        if self.dst_points.len() < self.num_points {
+            return Ok((
+                StepResult::Continue,
+                Some(CalibratorStatus::Progress {
+                    current: (self.src_points.len() + self.dst_points.len()) as u64,
+                    max: (self.num_points * 2) as u64,
+                    message: self.prompt(data),
+                }),
+            ));
+        }
+
+        let Some(offset) = solve_point_pairs(&self.src_points, &self.dst_points) else {
+            log::info!("Points are too close to a line, please start over.");
+            self.src_points.clear();
+            self.dst_points.clear();
+            log::info!("{}", self.prompt(data));
+            return Ok((StepResult::Continue, None));
+        };
+
+        let rms_error = point_pairs_rms(&self.src_points, &self.dst_points, &offset);
+        log::info!(
+            "Calibration done. Offset: {} (RMS error: {:.3}m)",
+            offset,
+            rms_error
+        );
+
+        let dst_origin = data
+            .get_device_origin(self.dst_dev)
+            .context("Unable to get DST_DEV origin")?;
+        let dst_root = TransformD::from(
+            dst_origin
+                .get_offset()
+                .context("Unable to get DST origin offset")?,
+        );
+        let full_offset = offset * dst_root;
+        journal::set_origin_offset(&dst_origin, full_offset, "points")
+            .context("Unable to set DST origin offset")?;
+
+        let src_origin = data
+            .get_device_origin(self.src_dev)
+            .context("Unable to get SRC_DEV origin")?;
+        let src_root = TransformD::from(
+            src_origin
+                .get_offset()
+                .context("Unable to get SRC origin offset")?,
+        );
+        match data.save_calibration(
+            &self.profile,
+            src_origin.id as _,
+            dst_origin.id as _,
+            full_offset * src_root.inverse(),
+            OffsetType::TrackingOrigin,
+            SaveInfo {
+                src_device: Some(self.src_dev),
+                dst_device: Some(self.dst_dev),
+                samples: Some(self.num_points as _),
+                rms_error: Some(rms_error),
+            },
+        ) {
+            Ok(_) => {
+                log::info!("Saved calibration. Use `motoc continue` on next startup to use this.")
+            }
+            Err(e) => log::warn!("Could not save calibration: {}", e),
+        }
+
+        Ok((StepResult::End, None))
+    }
+
+    fn finish(&mut self, _data: &mut crate::common::CalibratorData) -> Result<()> {
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
     common::UNIT,
     error::{Error, ResultExt},
     helpers_xr::SpaceLocationConvert,
+    journal,
     transformd::TransformD,
 };
 
@@ -122,8 +123,7 @@ impl Calibrator for RecenterMethod {
             self.space
         );
 
-        data.monado
-            .set_reference_space_offset(mnd_space, new_reference.into())
+        journal::set_space_offset(data.monado, mnd_space, new_reference, "recenter")
             .context("Unable to set reference space offset")?;
 
         Ok((StepResult::End, None))
@@ -1,12 +1,14 @@
 use nalgebra::{Dyn, Matrix3, OMatrix, Rotation3, RowVector3, UnitQuaternion, Vector3, U1, U3};
 
 use libmonado as mnd;
+use openxr as xr;
 
 use crate::{
-    calibrator::{CalibratorStatus, OffsetMethod, StepResult},
-    common::OffsetType,
+    calibrator::{CalibratorStatus, OffsetMethod, RigidPair, StepResult},
+    common::{OffsetType, SaveInfo},
     error::{Error, ResultExt},
-    helpers_xr::SpaceLocationConvert,
+    helpers_xr::{locate_hand_joints, HandJointSource, SpaceLocationConvert},
+    journal,
     transformd::TransformD,
 };
 
@@ -20,7 +22,7 @@ struct DeltaRotSample {
 }
 
 impl DeltaRotSample {
-    fn new(new: &Sample, old: &Sample) -> Option<Self> {
+    fn new(new: &Sample, old: &Sample, min_angle: f64) -> Option<Self> {
         let delta_a = new.a.basis * old.a.basis.transpose();
         let delta_b = new.b.basis * old.b.basis.transpose();
 
@@ -30,8 +32,8 @@ impl DeltaRotSample {
         let samp_a = axis_from_mat3a(delta_a.matrix());
         let samp_b = axis_from_mat3a(delta_b.matrix());
 
-        if angle_a < 0.4
-            || angle_b < 0.4
+        if angle_a < min_angle
+            || angle_b < min_angle
             || samp_a.norm_squared() < 0.1
             || samp_b.norm_squared() < 0.1
         {
@@ -57,6 +59,35 @@ fn angle_from_mat3a(mat: &Matrix3<f64>) -> f64 {
     ((mat.row(0)[0] + mat.row(1)[1] + mat.row(2)[2] - 1.0) / 2.0).acos()
 }
 
+// joints reporting a larger radius than this are considered bogus
+const MAX_JOINT_RADIUS: f32 = 0.05;
+
+enum SampleSource {
+    Device(usize),
+    HandJoint {
+        source: HandJointSource,
+        tracker: xr::HandTracker,
+        ext_hand_tracking: xr::raw::HandTrackingEXT,
+        tracking_origin: u32,
+    },
+}
+
+// consecutive frames of moving together before the countdown starts
+const ARM_FRAMES: u32 = 10;
+// consecutive frames of moving apart before collection is paused
+const PAUSE_FRAMES: u32 = 3;
+const COUNTDOWN_SECS: i64 = 3;
+
+enum Phase {
+    // waiting for the devices to move together
+    Armed,
+    // the devices move together; collection starts at the given time in nanoseconds
+    Countdown(i64),
+    Collecting,
+    // the devices came apart mid-run
+    Paused,
+}
+
 #[derive(Default, Clone, Copy)]
 struct Sample {
     a: TransformD,
@@ -68,14 +99,23 @@ struct Sample {
 /// implements the math from OpenVR-SpaceCalibrator by pushrax
 /// https://github.com/pushrax/OpenVR-SpaceCalibrator/blob/master/math.pdf
 pub struct SampledMethod {
-    src_dev: usize,
+    src: SampleSource,
     dst_dev: usize,
     samples: Vec<Sample>,
     maintain: bool,
     num_samples: usize,
     profile: String,
+    prior: Option<TransformD>,
+    phase: Phase,
+    rigid: RigidPair,
+    together: u32,
+    apart: u32,
 }
 
+// how far a refined continuous offset may stray from the prior before it is rejected
+const PRIOR_MAX_DISTANCE: f64 = 0.05;
+const PRIOR_MAX_ANGLE_DEG: f64 = 10.0;
+
 impl SampledMethod {
     pub fn new(
         src_dev: usize,
@@ -85,22 +125,127 @@ impl SampledMethod {
         profile: String,
     ) -> Self {
         Self {
-            src_dev,
+            src: SampleSource::Device(src_dev),
             dst_dev,
             samples: Vec::with_capacity(1000),
             maintain,
             num_samples: samples as _,
             profile,
+            prior: None,
+            phase: Phase::Armed,
+            rigid: RigidPair::default(),
+            together: 0,
+            apart: 0,
         }
     }
 
-    fn collect_samples(&mut self, data: &mut crate::common::CalibratorData) -> Result<()> {
-        let new_a = data.devices[self.src_dev]
-            .space
-            .locate(&data.stage, data.now)
-            .context("Unable to locate SRC_DEV in STAGE")?
-            .into_transformd()
-            .context("SRC_DEV pose does not translate to TransformD")?;
+    /// Treats the sampled run as a refinement of a known B-to-A offset, e.g. a mount preset.
+    /// A result that strays too far from it is discarded in favor of the prior.
+    pub fn refine(mut self, prior: TransformD) -> Self {
+        self.prior = Some(prior);
+        self
+    }
+
+    /// Starts collecting samples right away, instead of waiting for the devices to move together.
+    pub fn start_immediately(mut self) -> Self {
+        self.phase = Phase::Collecting;
+        self
+    }
+
+    /// Uses a hand tracking joint as the source instead of a device.
+    ///
+    /// `tracking_origin` is the origin the hand tracking data is reported in, usually the HMD's.
+    /// Continuous mode is not available, as hand tracking is too noisy to maintain an offset.
+    pub fn with_hand_source<G>(
+        session: &xr::Session<G>,
+        source: HandJointSource,
+        tracking_origin: u32,
+        dst_dev: usize,
+        samples: u32,
+        profile: String,
+    ) -> Result<Self> {
+        let Some(ext_hand_tracking) = session.instance().exts().ext_hand_tracking else {
+            return Err(Error::MissingExtension("EXT_hand_tracking"));
+        };
+
+        let tracker = session
+            .create_hand_tracker(source.hand)
+            .context("Unable to create hand tracker")?;
+
+        Ok(Self {
+            src: SampleSource::HandJoint {
+                source,
+                tracker,
+                ext_hand_tracking,
+                tracking_origin,
+            },
+            dst_dev,
+            samples: Vec::with_capacity(1000),
+            maintain: false,
+            num_samples: samples as _,
+            profile,
+            prior: None,
+            phase: Phase::Armed,
+            rigid: RigidPair::default(),
+            together: 0,
+            apart: 0,
+        })
+    }
+
+    fn locate_src(&self, data: &crate::common::CalibratorData) -> Result<TransformD> {
+        match &self.src {
+            SampleSource::Device(src_dev) => data.devices[*src_dev]
+                .space
+                .locate(&data.stage, data.now)
+                .context("Unable to locate SRC_DEV in STAGE")?
+                .into_transformd()
+                .context("SRC_DEV pose does not translate to TransformD"),
+            SampleSource::HandJoint {
+                source,
+                tracker,
+                ext_hand_tracking,
+                ..
+            } => {
+                let Some(locations) =
+                    locate_hand_joints(ext_hand_tracking, tracker, &data.stage, data.now)?
+                else {
+                    return Err(Error::DeviceNotTracked);
+                };
+
+                let loc = &locations[source.joint.into_raw() as usize];
+                if loc.radius <= 0.0 || loc.radius > MAX_JOINT_RADIUS {
+                    return Err(Error::DeviceNotTracked);
+                }
+
+                xr::SpaceLocation {
+                    location_flags: loc.location_flags,
+                    pose: loc.pose,
+                }
+                .into_transformd()
+            }
+        }
+    }
+
+    fn src_origin<'a>(
+        &self,
+        data: &crate::common::CalibratorData<'a>,
+    ) -> Result<mnd::TrackingOrigin<'a>> {
+        match &self.src {
+            SampleSource::Device(src_dev) => data.get_device_origin(*src_dev),
+            SampleSource::HandJoint {
+                tracking_origin, ..
+            } => data
+                .tracking_origins
+                .get(*tracking_origin as usize)
+                .cloned()
+                .ok_or(Error::TrackingOriginNotFound {
+                    tracking_origin: *tracking_origin,
+                }),
+        }
+    }
+
+    fn locate_pair(&self, data: &crate::common::CalibratorData) -> Result<Sample> {
+        let new_a = self.locate_src(data)?;
 
         let new_b = data.devices[self.dst_dev]
             .space
@@ -115,18 +260,81 @@ impl SampledMethod {
                 .context("Unable to get STAGE reference")?,
         );
 
-        let (new_a, new_b) = (stage * new_a, stage * new_b);
-        self.samples.push(Sample { a: new_a, b: new_b });
+        Ok(Sample {
+            a: stage * new_a,
+            b: stage * new_b,
+        })
+    }
 
-        Ok(())
+    // advances the armed/paused state machine; returns a status while not collecting
+    fn update_phase(
+        &mut self,
+        data: &crate::common::CalibratorData,
+        sample: Option<Sample>,
+    ) -> Option<CalibratorStatus> {
+        match self.rigid.push(sample.map(|s| (s.a, s.b))) {
+            Some(mismatch) if mismatch.is_rigid() => {
+                self.together += 1;
+                self.apart = 0;
+            }
+            Some(_) => {
+                self.together = 0;
+                self.apart += 1;
+            }
+            None if sample.is_none() => self.together = 0,
+            None => {}
+        }
+
+        let now = data.now.as_nanos();
+
+        match self.phase {
+            Phase::Armed => {
+                if self.together >= ARM_FRAMES {
+                    self.phase = Phase::Countdown(now + COUNTDOWN_SECS * 1_000_000_000);
+                }
+            }
+            Phase::Countdown(deadline) => {
+                if self.together == 0 {
+                    self.phase = Phase::Armed;
+                } else if now >= deadline {
+                    log::info!("Devices are moving together, collecting samples.");
+                    self.phase = Phase::Collecting;
+                }
+            }
+            Phase::Collecting => {
+                if self.apart >= PAUSE_FRAMES {
+                    log::warn!("Devices stopped moving together, pausing.");
+                    self.phase = Phase::Paused;
+                }
+            }
+            Phase::Paused => {
+                if self.together >= ARM_FRAMES {
+                    log::info!("Devices are moving together again, resuming.");
+                    self.phase = Phase::Collecting;
+                }
+            }
+        }
+
+        let message = match self.phase {
+            Phase::Armed => String::from("Waiting for the devices to move together..."),
+            Phase::Countdown(deadline) => format!(
+                "Starting in {}s, keep moving...",
+                (deadline - now + 999_999_999) / 1_000_000_000
+            ),
+            Phase::Collecting => return None,
+            Phase::Paused => String::from("Devices came apart. Move them together to resume."),
+        };
+        Some(CalibratorStatus::Spinner { message })
     }
 
-    fn calibrate_rotation(&self) -> Rotation3<f64> {
+    fn calibrate_rotation(&self, min_angle: f64) -> Rotation3<f64> {
         let mut deltas = Vec::with_capacity(self.samples.len());
 
         for i in 0..self.samples.len() {
             for j in 0..i {
-                if let Some(delta) = DeltaRotSample::new(&self.samples[i], &self.samples[j]) {
+                if let Some(delta) =
+                    DeltaRotSample::new(&self.samples[i], &self.samples[j], min_angle)
+                {
                     deltas.push(delta);
                 }
             }
@@ -248,11 +456,31 @@ impl SampledMethod {
             origin: out_pos,
         }
     }
+
+    // how far the per-sample B-to-A positions stray from their average, in meters
+    fn b_to_a_rms(&self, offset: &TransformD, avg: &TransformD) -> f64 {
+        let sum = self
+            .samples
+            .iter()
+            .map(|samp| ((*offset * samp.b).inverse() * samp.a).origin - avg.origin)
+            .map(|d| d.norm_squared())
+            .sum::<f64>();
+        (sum / self.samples.len().max(1) as f64).sqrt()
+    }
 }
 
 impl Calibrator for SampledMethod {
     fn init(&mut self, _: &mut crate::common::CalibratorData) -> Result<StepResult> {
-        log::info!("Move the two devices together!");
+        if let SampleSource::HandJoint { source, .. } = &self.src {
+            log::info!("Using {} as the source.", source);
+        }
+        if matches!(self.phase, Phase::Armed) {
+            log::info!(
+                "Attach the two devices, then move them around. Sampling starts on its own."
+            );
+        } else {
+            log::info!("Move the two devices together!");
+        }
 
         Ok(StepResult::Continue)
     }
@@ -262,7 +490,15 @@ impl Calibrator for SampledMethod {
         data: &mut crate::common::CalibratorData,
     ) -> Result<(StepResult, Option<CalibratorStatus>)> {
         if self.samples.len() < self.num_samples {
-            let _ = self.collect_samples(data);
+            let sample = self.locate_pair(data).ok();
+
+            if let Some(status) = self.update_phase(data, sample) {
+                return Ok((StepResult::Continue, Some(status)));
+            }
+
+            if let Some(sample) = sample {
+                self.samples.push(sample);
+            }
 
             return Ok((
                 StepResult::Continue,
@@ -279,7 +515,7 @@ impl Calibrator for SampledMethod {
         };
 
         // sampling done, calculate
-        let rot = self.calibrate_rotation();
+        let rot = self.calibrate_rotation(data.tuning.min_sample_rotation);
         let pos = self
             .calibrate_translation(&rot)
             .context("Unable to calibrate translation")?;
@@ -291,8 +527,9 @@ impl Calibrator for SampledMethod {
         if pos.norm_squared() > 10000.0 {
             log::info!("Calibration failed, retrying...");
             self.samples.clear();
-            dst_origin
-                .set_offset(TransformD::default().into())
+            self.rigid = RigidPair::default();
+            self.phase = Phase::Armed;
+            journal::set_origin_offset(&dst_origin, TransformD::default(), "calibrate")
                 .context("Unable to set DST origin offset")?;
             return Ok((StepResult::Continue, None));
         }
@@ -302,7 +539,22 @@ impl Calibrator for SampledMethod {
             origin: pos,
         };
 
-        log::info!("Calibration done. Offset: {}", offset);
+        let b_to_a = self.avg_b_to_a_offset(&offset);
+        let info = SaveInfo {
+            src_device: match self.src {
+                SampleSource::Device(src_dev) => Some(src_dev),
+                SampleSource::HandJoint { .. } => None,
+            },
+            dst_device: Some(self.dst_dev),
+            samples: Some(self.samples.len() as _),
+            rms_error: Some(self.b_to_a_rms(&offset, &b_to_a)),
+        };
+
+        log::info!(
+            "Calibration done. Offset: {} (RMS error: {:.3}m)",
+            offset,
+            info.rms_error.unwrap_or_default()
+        );
 
         let dst_root = TransformD::from(
             dst_origin
@@ -310,19 +562,43 @@ impl Calibrator for SampledMethod {
                 .context("Unable to get DST origin offset")?,
         );
         let full_offset = offset * dst_root;
-        dst_origin
-            .set_offset(full_offset.into())
+        journal::set_origin_offset(&dst_origin, full_offset, "calibrate")
             .context("Unable to set DST origin offset")?;
 
-        if self.maintain {
-            let offset = self.avg_b_to_a_offset(&offset);
+        let maintain_src = match self.src {
+            SampleSource::Device(src_dev) if self.maintain => Some(src_dev),
+            _ => None,
+        };
+
+        if let Some(src_dev) = maintain_src {
+            let mut offset = b_to_a;
+
+            if let Some(prior) = self.prior {
+                let delta = prior.inverse() * offset;
+                if delta.origin.norm() > PRIOR_MAX_DISTANCE
+                    || delta.basis.angle().to_degrees() > PRIOR_MAX_ANGLE_DEG
+                {
+                    log::warn!(
+                        "Refined offset {} is too far from the preset, keeping the preset.",
+                        offset
+                    );
+                    offset = prior;
+                } else {
+                    log::info!(
+                        "Refined the preset by {:.3}m {:.1}°",
+                        delta.origin.norm(),
+                        delta.basis.angle().to_degrees()
+                    );
+                }
+            }
 
             match data.save_calibration(
                 &self.profile,
-                self.src_dev,
+                src_dev,
                 self.dst_dev,
                 offset,
                 OffsetType::Device,
+                info,
             ) {
                 Ok(_) => log::info!(
                     "Saved calibration. Use `motoc continue` on next startup to use this."
@@ -332,16 +608,16 @@ impl Calibrator for SampledMethod {
 
             Ok((
                 StepResult::Replace(Box::new(OffsetMethod::new_internal(
-                    self.src_dev,
+                    src_dev,
                     self.dst_dev,
                     offset,
-                    0.02,
+                    data.tuning.lerp_factor,
                 ))),
                 None,
             ))
         } else {
-            let src_origin = data
-                .get_device_origin(self.src_dev)
+            let src_origin = self
+                .src_origin(data)
                 .context("Unable to get SRC_DEV origin")?;
             let src_root = TransformD::from(
                 src_origin
@@ -354,6 +630,7 @@ impl Calibrator for SampledMethod {
                 dst_origin.id as _,
                 full_offset * src_root.inverse(),
                 OffsetType::TrackingOrigin,
+                info,
             ) {
                 Ok(_) => log::info!(
                     "Saved calibration. Use `motoc continue` on next startup to use this."
@@ -0,0 +1,117 @@
+use std::{
+    io::{self, ErrorKind, Read},
+    net::{TcpListener, TcpStream},
+    os::unix::net::{UnixListener, UnixStream},
+    time::{Duration, Instant},
+};
+
+/// A listening socket whose clients `Clients` serves.
+pub(crate) trait Listener {
+    type Stream: Read;
+
+    /// Accepts a client and makes its stream non-blocking.
+    fn accept_nonblocking(&self) -> io::Result<Self::Stream>;
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+
+    fn accept_nonblocking(&self) -> io::Result<TcpStream> {
+        let (stream, _) = self.accept()?;
+        stream.set_nonblocking(true)?;
+        Ok(stream)
+    }
+}
+
+impl Listener for UnixListener {
+    type Stream = UnixStream;
+
+    fn accept_nonblocking(&self) -> io::Result<UnixStream> {
+        let (stream, _) = self.accept()?;
+        stream.set_nonblocking(true)?;
+        Ok(stream)
+    }
+}
+
+pub(crate) struct Connection<S> {
+    pub stream: S,
+    /// what the client sent and was not handled yet
+    pub buf: Vec<u8>,
+    last_read: Instant,
+}
+
+/// The clients of a non-blocking listener, with what each sent so far.
+/// Never blocks, so it can be polled from the main loop.
+pub(crate) struct Clients<L: Listener> {
+    listener: L,
+    connections: Vec<Connection<L::Stream>>,
+    // clients that send nothing for this long are dropped, so they cannot hold an fd forever
+    idle_timeout: Duration,
+    name: &'static str,
+}
+
+impl<L: Listener> Clients<L> {
+    /// `listener` must be non-blocking. `name` is used in log messages.
+    pub fn new(listener: L, idle_timeout: Duration, name: &'static str) -> Self {
+        Self {
+            listener,
+            connections: Vec::new(),
+            idle_timeout,
+            name,
+        }
+    }
+
+    pub fn listener(&self) -> &L {
+        &self.listener
+    }
+
+    /// Accepts new clients and reads what they sent. `handle` is called for each
+    /// client and returns whether to keep it. Clients that hung up or stayed idle
+    /// for too long are dropped after that.
+    pub fn poll(&mut self, mut handle: impl FnMut(&mut Connection<L::Stream>) -> bool) {
+        loop {
+            match self.listener.accept_nonblocking() {
+                Ok(stream) => self.connections.push(Connection {
+                    stream,
+                    buf: Vec::new(),
+                    last_read: Instant::now(),
+                }),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::warn!("{} socket: {}", self.name, e);
+                    break;
+                }
+            }
+        }
+
+        let (name, idle_timeout) = (self.name, self.idle_timeout);
+        self.connections.retain_mut(|conn| {
+            let open = read_available(conn);
+            if !handle(conn) || !open {
+                return false;
+            }
+            if conn.last_read.elapsed() > idle_timeout {
+                log::debug!("Dropping idle {} client.", name);
+                return false;
+            }
+            true
+        });
+    }
+}
+
+/// Reads what the client sent so far. Returns false once it hung up.
+fn read_available<S: Read>(conn: &mut Connection<S>) -> bool {
+    let mut chunk = [0u8; 4096];
+    loop {
+        match conn.stream.read(&mut chunk) {
+            Ok(0) => return false,
+            Ok(n) => {
+                conn.buf.extend_from_slice(&chunk[..n]);
+                conn.last_read = Instant::now();
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return true,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(_) => return false,
+        }
+    }
+}
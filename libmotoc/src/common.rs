@@ -1,11 +1,22 @@
-use std::{fs::File, sync::LazyLock};
+use std::{
+    collections::HashMap,
+    sync::LazyLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use libmonado as mnd;
+use libmonado::{self as mnd, DeviceLogic};
 use nalgebra::{UnitVector3, Vector3};
 use openxr as xr;
+use openxr_mndx_xdev_space::{SessionXDevExtensionMNDX, XDevList};
 use serde::{Deserialize, Serialize};
 
+use crate::aliases::{NameKind, NameMatcher};
+use crate::config::Tuning;
 use crate::error::Error;
+use crate::helpers_xr::SpaceLocationConvert;
+use crate::journal;
+use crate::profiles::{add_to_profile, list_profiles, read_profile, Profile, ProfileMatch};
+use crate::telemetry::Telemetry;
 use crate::transformd::TransformD;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -56,6 +67,8 @@ pub struct Device<'a> {
     pub tracking_origin: u32,
     pub space: xr::Space,
     pub inner: mnd::Device<'a>,
+    /// false once the device is gone. It keeps its index in case it comes back
+    pub connected: bool,
 }
 
 pub struct CalibratorData<'a> {
@@ -66,14 +79,172 @@ pub struct CalibratorData<'a> {
     pub local: xr::Space,
     pub view: xr::Space,
     pub now: xr::Time,
+    pub tuning: Tuning,
+    pub telemetry: Telemetry,
+    xdevs: XDevList,
+    generation: u64,
+}
+
+/// What `CalibratorData::refresh` found, by index into `CalibratorData::devices`.
+#[derive(Debug, Default)]
+pub struct DeviceChanges {
+    /// new devices, and ones that came back
+    pub added: Vec<usize>,
+    pub removed: Vec<usize>,
+    pub origins_changed: bool,
+}
+
+impl DeviceChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && !self.origins_changed
+    }
+}
+
+/// Where live devices go when merging them into the known ones by serial.
+#[derive(Debug, PartialEq)]
+pub struct DeviceMerge {
+    /// for each live device, the index it replaces, or `None` to append it
+    pub slots: Vec<Option<usize>>,
+    /// connected devices that are not live anymore
+    pub removed: Vec<usize>,
+}
+
+/// Matches live serials to the known ones, given as serial and whether it was connected.
+pub fn merge_devices(known: &[(&str, bool)], live: &[&str]) -> DeviceMerge {
+    let slots = live
+        .iter()
+        .map(|serial| known.iter().position(|(s, _)| s == serial))
+        .collect();
+    let removed = known
+        .iter()
+        .enumerate()
+        .filter(|(_, (serial, connected))| *connected && !live.contains(serial))
+        .map(|(i, _)| i)
+        .collect();
+    DeviceMerge { slots, removed }
+}
+
+/// The devices that Monado has and OpenXR can create a space for.
+fn enumerate_devices<'a>(xdevs: &XDevList, monado: &'a mnd::Monado) -> Result<Vec<Device<'a>>> {
+    let mut serial_space = HashMap::new();
+    for xdev in xdevs.enumerate_xdevs()?.into_iter() {
+        if !xdev.can_create_space() {
+            continue;
+        }
+        serial_space.insert(
+            xdev.serial().to_string(),
+            xdev.create_space(xr::Posef::IDENTITY)?,
+        );
+    }
+
+    let mut devices = vec![];
+    for dev in monado.devices()?.into_iter() {
+        let serial = dev.serial()?;
+        let Some(space) = serial_space.remove(serial.as_str()) else {
+            continue;
+        };
+
+        devices.push(Device {
+            tracking_origin: dev.get_info_u32(mnd::MndProperty::PropertyTrackingOriginU32)?,
+            serial,
+            space,
+            index: dev.index,
+            inner: dev,
+            connected: true,
+        });
+    }
+    Ok(devices)
 }
 
 impl<'a> CalibratorData<'a> {
+    pub fn new<G>(
+        session: &xr::Session<G>,
+        monado: &'a mnd::Monado,
+        tuning: Tuning,
+    ) -> Result<Self> {
+        let xdevs = session.get_xdev_list()?;
+        Ok(Self {
+            monado,
+            devices: enumerate_devices(&xdevs, monado)?,
+            tracking_origins: monado.tracking_origins()?.into_iter().collect(),
+            stage: session
+                .create_reference_space(xr::ReferenceSpaceType::STAGE, xr::Posef::IDENTITY)?,
+            local: session
+                .create_reference_space(xr::ReferenceSpaceType::LOCAL, xr::Posef::IDENTITY)?,
+            view: session
+                .create_reference_space(xr::ReferenceSpaceType::VIEW, xr::Posef::IDENTITY)?,
+            now: session.instance().now()?,
+            tuning,
+            telemetry: Telemetry::default(),
+            generation: xdevs.get_generation()?,
+            xdevs,
+        })
+    }
+
+    /// Picks up devices and tracking origins that came or went. Devices keep their
+    /// index, so calibrators can hold on to it.
+    pub fn refresh(&mut self) -> Result<DeviceChanges> {
+        let mut changes = DeviceChanges::default();
+
+        let origins: Vec<_> = self.monado.tracking_origins()?.into_iter().collect();
+        changes.origins_changed = origins
+            .iter()
+            .map(|o| (o.id, &o.name))
+            .ne(self.tracking_origins.iter().map(|o| (o.id, &o.name)));
+        self.tracking_origins = origins;
+
+        let xdevs = self.xdevs.session().get_xdev_list()?;
+        let generation = xdevs.get_generation()?;
+        if generation == self.generation {
+            return Ok(changes);
+        }
+        let live = enumerate_devices(&xdevs, self.monado)?;
+        self.xdevs = xdevs;
+        self.generation = generation;
+
+        let known: Vec<(&str, bool)> = self
+            .devices
+            .iter()
+            .map(|d| (d.serial.as_str(), d.connected))
+            .collect();
+        let serials: Vec<&str> = live.iter().map(|d| d.serial.as_str()).collect();
+        let merge = merge_devices(&known, &serials);
+
+        for &i in merge.removed.iter() {
+            self.devices[i].connected = false;
+        }
+        changes.removed = merge.removed;
+        for (device, slot) in live.into_iter().zip(merge.slots) {
+            match slot {
+                Some(i) => {
+                    if !self.devices[i].connected {
+                        changes.added.push(i);
+                    }
+                    self.devices[i] = device;
+                }
+                None => {
+                    changes.added.push(self.devices.len());
+                    self.devices.push(device);
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    /// The devices that are connected, with their index.
+    pub fn connected_devices(&self) -> impl Iterator<Item = (usize, &Device<'a>)> {
+        self.devices.iter().enumerate().filter(|(_, d)| d.connected)
+    }
+
     pub fn find_device(&self, serial_or_id: &str) -> Option<usize> {
         if let Ok(id) = serial_or_id.parse::<u32>() {
-            self.devices.iter().position(|d| d.index == id)
+            self.connected_devices()
+                .find(|(_, d)| d.index == id)
+                .map(|(i, _)| i)
         } else {
-            self.devices.iter().position(|d| d.serial == *serial_or_id)
+            self.connected_devices()
+                .find(|(_, d)| d.serial == *serial_or_id)
+                .map(|(i, _)| i)
         }
     }
 
@@ -89,6 +260,29 @@ impl<'a> CalibratorData<'a> {
         Ok(origin.clone())
     }
 
+    /// The tracking origin of the HMD.
+    pub fn head_tracking_origin(&self) -> Result<u32> {
+        let device = self.monado.device_from_role(mnd::DeviceRole::Head)?;
+        Ok(device.get_info_u32(mnd::MndProperty::PropertyTrackingOriginU32)?)
+    }
+
+    /// The tracking origin that hand tracking of the given hand is reported in.
+    /// Falls back to the origin of the HMD if there is no dedicated hand tracking device.
+    pub fn hand_tracking_origin(&self, hand: xr::HandEXT) -> Result<u32> {
+        let role = if hand == xr::HandEXT::LEFT {
+            mnd::DeviceRole::HandTrackingLeft
+        } else {
+            mnd::DeviceRole::HandTrackingRight
+        };
+
+        let device = self
+            .monado
+            .device_from_role(role)
+            .or_else(|_| self.monado.device_from_role(mnd::DeviceRole::Head))?;
+
+        Ok(device.get_info_u32(mnd::MndProperty::PropertyTrackingOriginU32)?)
+    }
+
     pub fn save_calibration(
         &self,
         profile: &str,
@@ -96,15 +290,8 @@ impl<'a> CalibratorData<'a> {
         dst: usize,
         offset: TransformD,
         offset_type: OffsetType,
+        info: SaveInfo,
     ) -> Result<()> {
-        let xdg_dirs = xdg::BaseDirectories::new();
-        let mut path = xdg_dirs.get_config_home().ok_or(Error::NoHomeDir)?;
-        path.push("motoc");
-        if !path.exists() {
-            std::fs::create_dir_all(&path)?;
-        }
-        path.push(format!("{}.json", profile));
-
         let (src_name, dst_name) = match offset_type {
             OffsetType::TrackingOrigin => (
                 self.tracking_origins[src].name.clone(),
@@ -116,36 +303,209 @@ impl<'a> CalibratorData<'a> {
             ),
         };
 
+        let (src_device, dst_device) = match offset_type {
+            OffsetType::TrackingOrigin => (info.src_device, info.dst_device),
+            OffsetType::Device => (Some(src), Some(dst)),
+        };
+        let origin_name = |dev: Option<usize>| {
+            let origin = self.devices.get(dev?)?.tracking_origin;
+            Some(self.tracking_origins.get(origin as usize)?.name.clone())
+        };
+        let (src_origin, dst_origin) = match offset_type {
+            OffsetType::TrackingOrigin => (Some(src_name.clone()), Some(dst_name.clone())),
+            OffsetType::Device => (origin_name(src_device), origin_name(dst_device)),
+        };
+
+        let meta = CalibrationMeta {
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            motoc_version: env!("CARGO_PKG_VERSION").to_string(),
+            src_device: src_device.and_then(|d| self.device_info(d)),
+            dst_device: dst_device.and_then(|d| self.device_info(d)),
+            src_origin,
+            dst_origin,
+            samples: info.samples,
+            rms_error: info.rms_error,
+        };
+
         let data = SavedCalibration {
             offset_type,
             src: src_name,
             dst: dst_name,
             offset,
+            meta,
         };
 
-        let f = File::create(path)?;
-        serde_json::to_writer(f, &data)?;
+        add_to_profile(profile, data)
+    }
+
+    pub fn load_calibration(&self, profile: &str) -> Result<Profile> {
+        read_profile(profile)
+    }
+
+    /// Finds a tracking origin by a saved name, as resolved by the matcher.
+    pub fn find_saved_origin(
+        &self,
+        name: &str,
+        matcher: &NameMatcher,
+    ) -> Option<&mnd::TrackingOrigin<'a>> {
+        let live: Vec<&str> = self
+            .tracking_origins
+            .iter()
+            .map(|o| o.name.as_str())
+            .collect();
+        let found = matcher.find(NameKind::Origin, name, &live)?;
+        self.tracking_origins.iter().find(|o| o.name == found)
+    }
+
+    /// Finds a device by a saved serial, as resolved by the matcher.
+    pub fn find_saved_device(&self, serial: &str, matcher: &NameMatcher) -> Option<usize> {
+        let live: Vec<&str> = self
+            .connected_devices()
+            .map(|(_, d)| d.serial.as_str())
+            .collect();
+        let found = matcher.find(NameKind::Device, serial, &live)?;
+        self.connected_devices()
+            .find(|(_, d)| d.serial == found)
+            .map(|(i, _)| i)
+    }
+
+    /// Applies a saved tracking origin offset relative to the current offset of its source.
+    pub fn apply_origin_offset(
+        &self,
+        saved: &SavedCalibration,
+        matcher: &NameMatcher,
+    ) -> Result<()> {
+        let src_transform = match self.find_saved_origin(&saved.src, matcher) {
+            Some(src_origin) => src_origin.get_offset()?.into(),
+            None => {
+                log::warn!(
+                    "Source origin \"{}\" not found, applying calibration with identity source. \
+                     If it was renamed, add an alias or use `motoc profile rebind`.",
+                    saved.src
+                );
+                TransformD::default()
+            }
+        };
+
+        let Some(dst_origin) = self.find_saved_origin(&saved.dst, matcher) else {
+            return Err(Error::UnknownTrackingOrigin(saved.dst.clone()));
+        };
+
+        journal::set_origin_offset(dst_origin, saved.offset * src_transform, "continue")?;
         Ok(())
     }
 
-    pub fn load_calibration(&self, profile: &str) -> Result<SavedCalibration> {
-        let xdg_dirs = xdg::BaseDirectories::new();
-        let mut path = xdg_dirs.get_config_home().ok_or(Error::NoHomeDir)?;
-        path.push("motoc");
-        path.push(format!("{}.json", profile));
+    /// Whether the device's pose is currently tracked.
+    pub fn is_tracked(&self, device: usize) -> bool {
+        let Some(device) = self.devices.get(device).filter(|d| d.connected) else {
+            return false;
+        };
+        device
+            .space
+            .locate(&self.stage, self.now)
+            .is_ok_and(|loc| loc.into_transformd().is_ok())
+    }
+
+    /// What keeps the profile from being applied in full: tracking origins and
+    /// devices that are not present, and devices that are not tracking.
+    pub fn missing_for(&self, profile: &Profile, matcher: &NameMatcher) -> Vec<String> {
+        let mut missing = Vec::new();
+        for saved in profile.offsets.iter() {
+            match saved.offset_type {
+                OffsetType::TrackingOrigin => {
+                    if self.find_saved_origin(&saved.dst, matcher).is_none() {
+                        missing.push(saved.dst.clone());
+                    }
+                }
+                OffsetType::Device => {
+                    for serial in [&saved.src, &saved.dst] {
+                        match self.find_saved_device(serial, matcher) {
+                            Some(idx) if self.is_tracked(idx) => {}
+                            Some(_) => missing.push(format!("{} (not tracking)", serial)),
+                            None => missing.push(serial.clone()),
+                        }
+                    }
+                }
+            }
+        }
+        missing.dedup();
+        missing
+    }
+
+    /// Picks the saved profile that best fits the connected devices and tracking origins.
+    /// Ties go to the most recently made profile.
+    pub fn select_profile(&self) -> Result<Option<ProfileChoice>> {
+        let serials: Vec<&str> = self
+            .connected_devices()
+            .map(|(_, d)| d.serial.as_str())
+            .collect();
+        let origins: Vec<&str> = self
+            .tracking_origins
+            .iter()
+            .map(|o| o.name.as_str())
+            .collect();
 
-        log::debug!(
-            "Will load calibration data from: {}",
-            path.to_string_lossy()
-        );
+        let aliases = NameMatcher::load(false).aliases;
+
+        let mut best: Option<ProfileChoice> = None;
+        let mut considered = 0;
+        for (name, profile) in list_profiles()? {
+            let Ok(profile) = profile else {
+                continue;
+            };
+            considered += 1;
+
+            let fit = profile.match_against(&serials, &origins, &aliases);
+            if fit.usable == 0 {
+                continue;
+            }
+            let better = best.as_ref().is_none_or(|b| {
+                (fit.score, profile.created()) > (b.fit.score, b.profile.created())
+            });
+            if better {
+                best = Some(ProfileChoice {
+                    name,
+                    profile,
+                    fit,
+                    considered: 0,
+                });
+            }
+        }
+
+        Ok(best.map(|b| ProfileChoice { considered, ..b }))
+    }
 
-        let f = File::open(path)?;
-        let data: SavedCalibration = serde_json::from_reader(f)?;
-        Ok(data)
+    fn device_info(&self, device: usize) -> Option<DeviceInfo> {
+        let device = self.devices.get(device)?;
+        Some(DeviceInfo {
+            name: device.inner.name.clone(),
+            serial: device.serial.clone(),
+        })
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// The profile picked by `CalibratorData::select_profile`, and why.
+pub struct ProfileChoice {
+    pub name: String,
+    pub profile: Profile,
+    pub fit: ProfileMatch,
+    /// number of readable profiles that were compared
+    pub considered: usize,
+}
+
+/// Details of a calibration run that are recorded in the profile.
+#[derive(Default)]
+pub struct SaveInfo {
+    /// the devices that were used, if the offset is between tracking origins
+    pub src_device: Option<usize>,
+    pub dst_device: Option<usize>,
+    pub samples: Option<u32>,
+    pub rms_error: Option<f64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OffsetType {
     /// Used for one-shot calibration
     TrackingOrigin,
@@ -159,4 +519,28 @@ pub struct SavedCalibration {
     pub src: String,
     pub dst: String,
     pub offset: TransformD,
+    #[serde(default)]
+    pub meta: CalibrationMeta,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub serial: String,
+}
+
+/// Where a saved calibration came from. Profiles from older versions have none of this.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalibrationMeta {
+    /// unix time in seconds, 0 if unknown
+    pub created: u64,
+    pub motoc_version: String,
+    pub src_device: Option<DeviceInfo>,
+    pub dst_device: Option<DeviceInfo>,
+    pub src_origin: Option<String>,
+    pub dst_origin: Option<String>,
+    pub samples: Option<u32>,
+    /// root-mean-square residual of the solve, in meters
+    pub rms_error: Option<f64>,
 }
@@ -0,0 +1,157 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, time::Duration};
+
+use openxr as xr;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::helpers_xr::EffectiveSpaceVelocity;
+use crate::profiles::config_dir;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Tuning values used by the calibrators. Every field can be set in `config.toml`,
+/// globally or for a single profile.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tuning {
+    /// samples taken by `calibrate` and `discover --calibrate` unless `--samples` is given
+    pub samples: u32,
+    /// smoothing of continuous mode after a calibration or `continue`. range (0, 1]
+    pub lerp_factor: f64,
+    /// smoothing of `motoc offset` unless `--lerp` is given. range (0, 1]
+    pub offset_lerp_factor: f64,
+    /// devices moving faster than this are ignored, in m/s
+    pub max_linear_speed: f64,
+    /// devices turning faster than this are ignored, in rad/s
+    pub max_angular_speed: f64,
+    /// continuous mode treats offsets further than this from the center as an anomaly, in meters
+    pub anomaly_distance: f64,
+    /// an anomaly that lasts this long resets the offset, in seconds
+    pub anomaly_reset_secs: f64,
+    /// sample pairs that rotated less than this are not used for calibration, in radians
+    pub min_sample_rotation: f64,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            samples: 500,
+            lerp_factor: 0.02,
+            offset_lerp_factor: 0.05,
+            max_linear_speed: 0.5f64.sqrt(),
+            max_angular_speed: 0.4f64.sqrt(),
+            anomaly_distance: 100.0,
+            anomaly_reset_secs: 5.0,
+            min_sample_rotation: 0.4,
+        }
+    }
+}
+
+impl Tuning {
+    /// Whether a device moves too fast for its pose to be trusted.
+    pub fn too_fast(&self, velocity: &xr::SpaceVelocity) -> bool {
+        let linear = velocity.effective_linear().norm() as f64;
+        let angular = velocity.effective_angular().norm() as f64;
+        linear > self.max_linear_speed || angular > self.max_angular_speed
+    }
+
+    pub fn anomaly_reset(&self) -> Duration {
+        Duration::try_from_secs_f64(self.anomaly_reset_secs.max(0.0)).unwrap_or(Duration::MAX)
+    }
+
+    /// Checks that every value is in its documented range.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.samples == 0 {
+            return Err(String::from("samples must be more than 0"));
+        }
+        for (name, value) in [
+            ("lerp_factor", self.lerp_factor),
+            ("offset_lerp_factor", self.offset_lerp_factor),
+        ] {
+            // NaN is in neither range
+            if !(value > 0.0 && value <= 1.0) {
+                return Err(format!("{} must be in (0, 1], not {}", name, value));
+            }
+        }
+        // far beyond anything useful, but keeps infinity out
+        for (name, value, max) in [
+            ("max_linear_speed", self.max_linear_speed, 100.0),
+            ("max_angular_speed", self.max_angular_speed, 100.0),
+            ("anomaly_distance", self.anomaly_distance, 10_000.0),
+            ("anomaly_reset_secs", self.anomaly_reset_secs, 86_400.0),
+            (
+                "min_sample_rotation",
+                self.min_sample_rotation,
+                std::f64::consts::PI,
+            ),
+        ] {
+            if !(0.0..=max).contains(&value) {
+                return Err(format!("{} must be in [0, {}], not {}", name, max, value));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The contents of `config.toml`. Top-level keys set the defaults, and
+/// `[profile.NAME]` tables override some of them for one profile.
+#[derive(Default, Deserialize)]
+pub struct Config {
+    #[serde(flatten)]
+    defaults: toml::Table,
+    #[serde(default)]
+    profile: BTreeMap<String, toml::Table>,
+}
+
+/// `$XDG_CONFIG_HOME/motoc/config.toml`
+pub fn config_path() -> Result<PathBuf> {
+    let mut path = config_dir()?;
+    path.push("config.toml");
+    Ok(path)
+}
+
+impl Config {
+    /// Reads `config.toml`, or an empty config if there is none.
+    pub fn load() -> Result<Self> {
+        let path = config_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text).map_err(|e| Error::InvalidConfig(e.to_string()))?;
+        // catch typos right away instead of on first use of the profile
+        for name in config.profile.keys() {
+            config.tuning(Some(name))?;
+        }
+        config.tuning(None)?;
+        Ok(config)
+    }
+
+    /// The tuning for a profile: built-in defaults, then the top-level keys,
+    /// then the profile's table.
+    pub fn tuning(&self, profile: Option<&str>) -> Result<Tuning> {
+        let mut table = self.defaults.clone();
+        if let Some(overrides) = profile.and_then(|name| self.profile.get(name)) {
+            table.extend(overrides.clone());
+        }
+        let at = profile.map_or(String::new(), |name| format!(" in [profile.{}]", name));
+        let tuning: Tuning =
+            toml::Value::Table(table)
+                .try_into()
+                .map_err(|e: toml::de::Error| {
+                    Error::InvalidConfig(format!("{}{}", e.message().trim(), at))
+                })?;
+        tuning
+            .validate()
+            .map_err(|e| Error::InvalidConfig(format!("{}{}", e, at)))?;
+        Ok(tuning)
+    }
+}
+
+/// Formats tuning values as `config.toml` would hold them.
+pub fn format_tuning(tuning: &Tuning) -> Result<String> {
+    toml::to_string(tuning).map_err(|e| Error::InvalidConfig(e.to_string()))
+}
@@ -0,0 +1,327 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::mpsc,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::clients::Clients;
+use crate::error::Error;
+use crate::transformd::TransformD;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// how long a client waits for the daemon to answer
+const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+// requests longer than this are refused, a line is never this long
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+// clients send their request right away and hang up once answered
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+fn default_profile() -> String {
+    String::from("last")
+}
+
+/// A command for `motoc daemon`. On the socket, it is the `method` and `params`
+/// of a JSON-RPC 2.0 request, one request per line.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    /// what the daemon is doing, see `DaemonStatus`
+    Status,
+    /// stop the running calibration, if any
+    Stop,
+    /// stop the daemon
+    Shutdown,
+    /// start a sampled calibration between two devices
+    Calibrate {
+        src: String,
+        dst: String,
+        #[serde(default)]
+        continuous: bool,
+        #[serde(default)]
+        samples: Option<u32>,
+        #[serde(default)]
+        no_wait: bool,
+        #[serde(default = "default_profile")]
+        profile: String,
+    },
+    /// load a profile. without one, the profile that best fits the connected devices
+    Continue {
+        #[serde(default)]
+        profile: Option<String>,
+        #[serde(default)]
+        fuzzy: bool,
+    },
+    /// set or move the offset of STAGE, LOCAL or a tracking origin
+    Adjust {
+        id: String,
+        #[serde(default)]
+        relative: bool,
+        #[serde(default)]
+        yaw: Option<f64>,
+        #[serde(default)]
+        x: Option<f64>,
+        #[serde(default)]
+        y: Option<f64>,
+        #[serde(default)]
+        z: Option<f64>,
+    },
+    /// recenter STAGE or LOCAL on the HMD
+    Recenter {
+        id: String,
+        #[serde(default)]
+        height: Option<String>,
+    },
+    /// find the floor level with hand tracking
+    Floor,
+    /// reset the offset of STAGE, LOCAL or a tracking origin
+    Reset { id: String },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    /// what was last started, while it is still running
+    pub calibrator: Option<String>,
+    /// the calibrator's own status line
+    pub message: Option<String>,
+    pub devices: Vec<String>,
+    pub tracking_origins: Vec<String>,
+    /// STAGE, LOCAL and each tracking origin by name
+    #[serde(default)]
+    pub offsets: BTreeMap<String, TransformD>,
+}
+
+/// The result of a successful request.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DaemonReply {
+    Status(DaemonStatus),
+    Message(String),
+}
+
+/// `$XDG_RUNTIME_DIR/motoc/daemon.sock`
+pub fn daemon_socket_path() -> Result<PathBuf> {
+    let xdg_dirs = xdg::BaseDirectories::new();
+    let mut path = xdg_dirs
+        .get_runtime_directory()
+        .map_err(|_| Error::NoRuntimeDir)?
+        .clone();
+    path.push("motoc");
+    path.push("daemon.sock");
+    Ok(path)
+}
+
+/// Sends one request to the running daemon and waits for the reply.
+pub fn call_daemon(request: &DaemonRequest) -> Result<DaemonReply> {
+    let path = daemon_socket_path()?;
+    let stream = UnixStream::connect(&path).map_err(|_| Error::DaemonNotRunning)?;
+    stream.set_read_timeout(Some(CALL_TIMEOUT))?;
+
+    let mut call = serde_json::to_value(request)?;
+    call["jsonrpc"] = json!("2.0");
+    call["id"] = json!(1);
+    let mut line = serde_json::to_string(&call)?;
+    line.push('\n');
+    (&stream).write_all(line.as_bytes())?;
+
+    let mut answer = String::new();
+    BufReader::new(&stream).read_line(&mut answer)?;
+    let mut answer: Value = serde_json::from_str(&answer)?;
+
+    if let Some(error) = answer.get("error") {
+        let message = error["message"].as_str().unwrap_or("unknown error");
+        return Err(Error::DaemonFailed(message.to_string()));
+    }
+    Ok(serde_json::from_value(answer["result"].take())?)
+}
+
+pub type CallResult = std::result::Result<DaemonReply, String>;
+
+enum Responder {
+    Socket { id: Value, stream: UnixStream },
+    Channel(mpsc::Sender<CallResult>),
+}
+
+/// A request read from a client. Answer it with `reply`.
+pub struct IncomingCall {
+    pub request: DaemonRequest,
+    responder: Responder,
+}
+
+impl IncomingCall {
+    /// A call from another frontend, e.g. D-Bus. The reply is sent to the returned receiver.
+    pub fn new(request: DaemonRequest) -> (Self, mpsc::Receiver<CallResult>) {
+        let (tx, rx) = mpsc::channel();
+        let call = Self {
+            request,
+            responder: Responder::Channel(tx),
+        };
+        (call, rx)
+    }
+
+    pub fn reply(self, result: CallResult) {
+        match self.responder {
+            Responder::Socket { id, stream } => {
+                let answer = match result {
+                    Ok(reply) => json!({ "jsonrpc": "2.0", "id": id, "result": reply }),
+                    Err(message) => error_json(id, SERVER_ERROR, &message),
+                };
+                send(&stream, &answer);
+            }
+            Responder::Channel(tx) => {
+                // the caller may have given up waiting
+                let _ = tx.send(result);
+            }
+        }
+    }
+}
+
+fn error_json(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn send(mut stream: &UnixStream, answer: &Value) {
+    let mut line = answer.to_string();
+    line.push('\n');
+    if let Err(e) = stream.write_all(line.as_bytes()) {
+        log::debug!("Could not answer daemon client: {}", e);
+    }
+}
+
+/// The daemon's end of the socket. Never blocks, so it can be polled from the main loop.
+pub struct DaemonServer {
+    clients: Clients<UnixListener>,
+    path: PathBuf,
+    queue: (mpsc::Sender<IncomingCall>, mpsc::Receiver<IncomingCall>),
+}
+
+impl Drop for DaemonServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl DaemonServer {
+    pub fn bind() -> Result<Self> {
+        let path = daemon_socket_path()?;
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(Error::DaemonRunning);
+            }
+            // left behind by a daemon that crashed
+            fs::remove_file(&path)?;
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        log::info!("Listening on {}", path.display());
+
+        Ok(Self {
+            clients: Clients::new(listener, IDLE_TIMEOUT, "Daemon"),
+            path,
+            queue: mpsc::channel(),
+        })
+    }
+
+    /// Lets other frontends queue calls, which `poll` returns along with the socket's.
+    pub fn queue(&self) -> mpsc::Sender<IncomingCall> {
+        self.queue.0.clone()
+    }
+
+    /// Accepts new clients and returns the requests that arrived since the last poll.
+    /// Malformed requests are answered right away.
+    pub fn poll(&mut self) -> Vec<IncomingCall> {
+        let mut calls: Vec<IncomingCall> = self.queue.1.try_iter().collect();
+        self.clients.poll(|conn| {
+            while let Some(end) = conn.buf.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = conn.buf.drain(..=end).collect();
+                if let Some(call) = parse_call(&conn.stream, &line) {
+                    calls.push(call);
+                }
+            }
+            if conn.buf.len() > MAX_REQUEST_BYTES {
+                send(
+                    &conn.stream,
+                    &error_json(Value::Null, INVALID_REQUEST, "request too long"),
+                );
+                return false;
+            }
+            true
+        });
+        calls
+    }
+}
+
+/// Params may be left out for methods that do not need any.
+pub fn parse_request(method: Value, params: Option<Value>) -> serde_json::Result<DaemonRequest> {
+    match params {
+        Some(params) => serde_json::from_value(json!({ "method": method, "params": params })),
+        None => serde_json::from_value(json!({ "method": method.clone() }))
+            .or_else(|_| serde_json::from_value(json!({ "method": method, "params": {} }))),
+    }
+}
+
+fn parse_call(stream: &UnixStream, line: &[u8]) -> Option<IncomingCall> {
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return None;
+    }
+
+    let mut value: Value = match serde_json::from_slice(line) {
+        Ok(value) => value,
+        Err(e) => {
+            send(
+                stream,
+                &error_json(Value::Null, PARSE_ERROR, &e.to_string()),
+            );
+            return None;
+        }
+    };
+
+    let id = value.get_mut("id").map(Value::take).unwrap_or(Value::Null);
+    if !value.get("method").is_some_and(Value::is_string) {
+        send(stream, &error_json(id, INVALID_REQUEST, "missing method"));
+        return None;
+    }
+
+    let request = parse_request(
+        value["method"].take(),
+        value.get_mut("params").map(Value::take),
+    );
+    match (request, stream.try_clone()) {
+        (Ok(request), Ok(stream)) => Some(IncomingCall {
+            request,
+            responder: Responder::Socket { id, stream },
+        }),
+        (Err(e), _) => {
+            let message = e.to_string();
+            let code = if message.starts_with("unknown variant") {
+                METHOD_NOT_FOUND
+            } else {
+                INVALID_PARAMS
+            };
+            send(stream, &error_json(id, code, &message));
+            None
+        }
+        (_, Err(e)) => {
+            log::warn!("Daemon socket: {}", e);
+            None
+        }
+    }
+}
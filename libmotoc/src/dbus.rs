@@ -0,0 +1,213 @@
+use std::{collections::HashMap, sync::mpsc};
+
+use nalgebra::UnitQuaternion;
+use zbus::{blocking, fdo, object_server::SignalEmitter};
+
+use crate::daemon::{DaemonReply, DaemonRequest, DaemonStatus, IncomingCall};
+use crate::error::Error;
+use crate::transformd::TransformD;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub const DBUS_NAME: &str = "io.github.galister.Motoc";
+pub const DBUS_PATH: &str = "/io/github/galister/Motoc";
+
+/// x, y, z in meters, then the rotation as a quaternion x, y, z, w
+pub type DbusOffset = (f64, f64, f64, f64, f64, f64, f64);
+
+fn dbus_offset(offset: &TransformD) -> DbusOffset {
+    let q = UnitQuaternion::from_rotation_matrix(&offset.basis);
+    let o = offset.origin;
+    (o.x, o.y, o.z, q.i, q.j, q.k, q.w)
+}
+
+/// `io.github.galister.Motoc1`. Methods are queued for the daemon's main loop,
+/// properties mirror what it last published.
+struct MotocInterface {
+    queue: mpsc::Sender<IncomingCall>,
+    state: DaemonStatus,
+}
+
+impl MotocInterface {
+    async fn call(&self, request: DaemonRequest) -> fdo::Result<String> {
+        let (call, reply) = IncomingCall::new(request);
+        self.queue
+            .send(call)
+            .map_err(|_| fdo::Error::Failed(String::from("motoc daemon is shutting down")))?;
+
+        // the main loop answers within a frame; don't hold up the bus meanwhile
+        match ::blocking::unblock(move || reply.recv()).await {
+            Ok(Ok(DaemonReply::Message(message))) => Ok(message),
+            Ok(Ok(DaemonReply::Status(_))) => Ok(String::new()),
+            Ok(Err(message)) => Err(fdo::Error::Failed(message)),
+            Err(_) => Err(fdo::Error::Failed(String::from("motoc daemon stopped"))),
+        }
+    }
+}
+
+#[zbus::interface(name = "io.github.galister.Motoc1")]
+impl MotocInterface {
+    /// Starts a sampled calibration. `samples` 0 uses the configured default.
+    async fn calibrate(
+        &self,
+        src: String,
+        dst: String,
+        continuous: bool,
+        samples: u32,
+        profile: String,
+    ) -> fdo::Result<String> {
+        self.call(DaemonRequest::Calibrate {
+            src,
+            dst,
+            continuous,
+            samples: (samples > 0).then_some(samples),
+            no_wait: false,
+            profile: if profile.is_empty() {
+                String::from("last")
+            } else {
+                profile
+            },
+        })
+        .await
+    }
+
+    /// Loads a profile, or the best fitting one if `profile` is empty.
+    #[zbus(name = "Continue")]
+    async fn continue_profile(&self, profile: String) -> fdo::Result<String> {
+        self.call(DaemonRequest::Continue {
+            profile: (!profile.is_empty()).then_some(profile),
+            fuzzy: false,
+        })
+        .await
+    }
+
+    /// Recenters STAGE or LOCAL. `height` is in meters, or "KEEP" or empty.
+    async fn recenter(&self, space: String, height: String) -> fdo::Result<String> {
+        self.call(DaemonRequest::Recenter {
+            id: space,
+            height: (!height.is_empty()).then_some(height),
+        })
+        .await
+    }
+
+    async fn floor(&self) -> fdo::Result<String> {
+        self.call(DaemonRequest::Floor).await
+    }
+
+    /// Resets STAGE, LOCAL or a tracking origin by id or name.
+    async fn reset(&self, id: String) -> fdo::Result<String> {
+        self.call(DaemonRequest::Reset { id }).await
+    }
+
+    async fn stop(&self) -> fdo::Result<String> {
+        self.call(DaemonRequest::Stop).await
+    }
+
+    /// What the running calibrator was started as, empty if none.
+    #[zbus(property)]
+    fn running(&self) -> String {
+        self.state.calibrator.clone().unwrap_or_default()
+    }
+
+    /// The running calibrator's status line.
+    #[zbus(property)]
+    fn status(&self) -> String {
+        self.state.message.clone().unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn devices(&self) -> Vec<String> {
+        self.state.devices.clone()
+    }
+
+    #[zbus(property)]
+    fn tracking_origins(&self) -> Vec<String> {
+        self.state.tracking_origins.clone()
+    }
+
+    /// STAGE, LOCAL and each tracking origin by name
+    #[zbus(property)]
+    fn offsets(&self) -> HashMap<String, DbusOffset> {
+        self.state
+            .offsets
+            .iter()
+            .map(|(name, offset)| (name.clone(), dbus_offset(offset)))
+            .collect()
+    }
+
+    /// Sent when a calibrator starts, stops or its status line changes.
+    #[zbus(signal)]
+    async fn calibrator_changed(
+        emitter: &SignalEmitter<'_>,
+        running: &str,
+        status: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// The daemon on the D-Bus session bus, as `io.github.galister.Motoc`.
+pub struct DbusService {
+    _conn: blocking::Connection,
+    iface: blocking::object_server::InterfaceRef<MotocInterface>,
+}
+
+impl DbusService {
+    /// Serves on the session bus. Calls are sent to `queue`, see `DaemonServer::queue`.
+    pub fn start(queue: mpsc::Sender<IncomingCall>) -> Result<Self> {
+        Self::serve(blocking::connection::Builder::session()?, queue)
+    }
+
+    /// Serves on the bus at `address`, e.g. a private one for testing.
+    pub fn start_at(address: &str, queue: mpsc::Sender<IncomingCall>) -> Result<Self> {
+        Self::serve(blocking::connection::Builder::address(address)?, queue)
+    }
+
+    fn serve(
+        builder: blocking::connection::Builder<'_>,
+        queue: mpsc::Sender<IncomingCall>,
+    ) -> Result<Self> {
+        let iface = MotocInterface {
+            queue,
+            state: DaemonStatus::default(),
+        };
+        let conn = builder
+            .name(DBUS_NAME)?
+            .serve_at(DBUS_PATH, iface)?
+            .build()?;
+        let iface = conn
+            .object_server()
+            .interface::<_, MotocInterface>(DBUS_PATH)?;
+        log::info!("Serving {} on D-Bus.", DBUS_NAME);
+
+        Ok(Self { _conn: conn, iface })
+    }
+
+    /// Updates the properties, notifying listeners of the ones that changed.
+    pub fn publish(&self, state: &DaemonStatus) -> Result<()> {
+        let emitter = self.iface.signal_emitter();
+        let mut iface = self.iface.get_mut();
+        if iface.state == *state {
+            return Ok(());
+        }
+        let old = std::mem::replace(&mut iface.state, state.clone());
+
+        zbus::block_on(async {
+            if old.calibrator != state.calibrator || old.message != state.message {
+                iface.running_changed(emitter).await?;
+                iface.status_changed(emitter).await?;
+                MotocInterface::calibrator_changed(emitter, &iface.running(), &iface.status())
+                    .await?;
+            }
+            if old.devices != state.devices {
+                iface.devices_changed(emitter).await?;
+            }
+            if old.tracking_origins != state.tracking_origins {
+                iface.tracking_origins_changed(emitter).await?;
+            }
+            if old.offsets != state.offsets {
+                iface.offsets_changed(emitter).await?;
+            }
+            Ok::<_, zbus::Error>(())
+        })?;
+        Ok(())
+    }
+}
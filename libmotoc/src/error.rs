@@ -8,8 +8,12 @@ use libmonado as mnd;
 pub enum Error {
     Xr(xr::sys::Result),
     Monado(mnd::MndResult),
-    DeviceNotFound { device: usize },
-    TrackingOriginNotFound { tracking_origin: u32 },
+    DeviceNotFound {
+        device: usize,
+    },
+    TrackingOriginNotFound {
+        tracking_origin: u32,
+    },
     NoHomeDir,
     Io(io::Error),
     Json(serde_json::Error),
@@ -18,7 +22,36 @@ pub enum Error {
     DeviceNotTracked,
     InvalidRecenterSpace(String),
     HandJointLocation(xr::sys::Result),
+    InvalidHandJoint(String),
     InvalidOperation,
+    AlreadyLevel,
+    NothingToCompare,
+    PresetNotFound(String),
+    ProfileNotFound(String),
+    ProfileExists(String),
+    InvalidProfileName(String),
+    UnsupportedProfileVersion(u64),
+    ConflictingRotation(String),
+    UnknownTrackingOrigin(String),
+    SnapshotNotFound(String),
+    SnapshotExists(String),
+    InvalidSnapshotName(String),
+    OffsetLocked {
+        target: String,
+        owner: String,
+    },
+    InvalidSvcProfile(String),
+    InvalidMonadoConfig(String),
+    NameNotInProfile(String),
+    InvalidConfig(String),
+    NoRuntimeDir,
+    DaemonNotRunning,
+    DaemonRunning,
+    DaemonFailed(String),
+    ServiceExists(String),
+    UnknownHost(String),
+    #[cfg(feature = "dbus")]
+    Dbus(zbus::Error),
     ParseFloat(std::num::ParseFloatError),
     Context(String, Box<Self>),
 }
@@ -46,7 +79,56 @@ impl fmt::Display for Error {
             Error::HandJointLocation(e) => {
                 write!(f, "failed to locate hand joints: {:?}", e)
             }
+            Error::InvalidHandJoint(joint) => {
+                write!(
+                    f,
+                    "invalid hand joint: {} (expected e.g. hand:left:wrist)",
+                    joint
+                )
+            }
             Error::InvalidOperation => write!(f, "invalid operation"),
+            Error::AlreadyLevel => write!(
+                f,
+                "the device is in the HMD's tracking origin, which is already level"
+            ),
+            Error::NothingToCompare => {
+                write!(f, "no devices from different tracking origins to compare")
+            }
+            Error::PresetNotFound(name) => write!(f, "no such mount preset: {}", name),
+            Error::ProfileNotFound(name) => write!(f, "no such profile: {}", name),
+            Error::ProfileExists(name) => write!(f, "profile already exists: {}", name),
+            Error::InvalidProfileName(name) => write!(f, "invalid profile name: {}", name),
+            Error::ConflictingRotation(msg) => {
+                write!(f, "conflicting rotations in profile: {}", msg)
+            }
+            Error::UnsupportedProfileVersion(version) => {
+                write!(
+                    f,
+                    "profile version {} is too new, please update motoc",
+                    version
+                )
+            }
+            Error::UnknownTrackingOrigin(name) => write!(f, "no such tracking origin: {}", name),
+            Error::SnapshotNotFound(name) => write!(f, "no such snapshot: {}", name),
+            Error::SnapshotExists(name) => write!(f, "snapshot already exists: {}", name),
+            Error::InvalidSnapshotName(name) => write!(f, "invalid snapshot name: {}", name),
+            Error::InvalidSvcProfile(msg) => {
+                write!(f, "not an OpenVR-SpaceCalibrator profile: {}", msg)
+            }
+            Error::InvalidMonadoConfig(msg) => write!(f, "invalid Monado config: {}", msg),
+            Error::NoRuntimeDir => write!(f, "XDG_RUNTIME_DIR is not set"),
+            Error::DaemonNotRunning => write!(f, "motoc daemon is not running"),
+            Error::DaemonRunning => write!(f, "motoc daemon is already running"),
+            Error::DaemonFailed(msg) => write!(f, "daemon: {}", msg),
+            Error::ServiceExists(path) => write!(f, "service already exists: {}", path),
+            Error::UnknownHost(host) => write!(f, "cannot resolve {}", host),
+            #[cfg(feature = "dbus")]
+            Error::Dbus(e) => write!(f, "D-Bus error: {}", e),
+            Error::InvalidConfig(msg) => write!(f, "invalid config.toml: {}", msg),
+            Error::NameNotInProfile(name) => write!(f, "\"{}\" is not used in the profile", name),
+            Error::OffsetLocked { target, owner } => {
+                write!(f, "{} is in use by {}, stop it first", target, owner)
+            }
             Error::ParseFloat(e) => write!(f, "parse float error: {}", e),
             Error::Context(ctx, inner) => write!(f, "{}: {}", ctx, inner),
         }
@@ -97,6 +179,13 @@ impl From<NulError> for Error {
     }
 }
 
+#[cfg(feature = "dbus")]
+impl From<zbus::Error> for Error {
+    fn from(e: zbus::Error) -> Self {
+        Error::Dbus(e)
+    }
+}
+
 impl From<std::num::ParseFloatError> for Error {
     fn from(e: std::num::ParseFloatError) -> Self {
         Error::ParseFloat(e)
@@ -1,4 +1,4 @@
-use std::{ffi::CString, str::FromStr};
+use std::{ffi::CString, fmt, mem::MaybeUninit, ptr, str::FromStr};
 
 use nalgebra::Vector3;
 use openxr::{self as xr};
@@ -130,3 +130,92 @@ impl EffectiveSpaceVelocity for xr::SpaceVelocity {
         }
     }
 }
+
+pub type HandJointLocations = [xr::HandJointLocationEXT; xr::HAND_JOINT_COUNT];
+
+/// Locates all joints of the given hand. Returns `None` while the hand is inactive.
+pub fn locate_hand_joints(
+    ext_hand_tracking: &xr::raw::HandTrackingEXT,
+    hand: &xr::HandTracker,
+    base: &xr::Space,
+    time: xr::Time,
+) -> Result<Option<HandJointLocations>> {
+    unsafe {
+        let mut locations: HandJointLocations = MaybeUninit::zeroed().assume_init();
+
+        let info = xr::sys::HandJointsLocateInfoEXT {
+            ty: xr::StructureType::HAND_JOINTS_LOCATE_INFO_EXT,
+            next: ptr::null(),
+            base_space: base.as_raw(),
+            time,
+        };
+
+        let mut result = xr::sys::HandJointLocationsEXT {
+            ty: xr::StructureType::HAND_JOINT_LOCATIONS_EXT,
+            next: ptr::null_mut(),
+            is_active: xr::sys::Bool32::from_raw(0),
+            joint_count: xr::HAND_JOINT_COUNT as _,
+            joint_locations: locations.as_mut_ptr(),
+        };
+
+        let res = (ext_hand_tracking.locate_hand_joints)(hand.as_raw(), &info, &mut result);
+
+        if res != xr::sys::Result::SUCCESS {
+            return Err(Error::HandJointLocation(res));
+        }
+
+        if !bool::from(result.is_active) {
+            return Ok(None);
+        }
+
+        Ok(Some(locations))
+    }
+}
+
+/// A single hand tracking joint, written as `hand:<left|right>:<joint>` on the command line,
+/// e.g. `hand:left:wrist` or `hand:right:index-tip`.
+#[derive(Clone, Copy, Debug)]
+pub struct HandJointSource {
+    pub hand: xr::HandEXT,
+    pub joint: xr::HandJointEXT,
+}
+
+impl HandJointSource {
+    /// Returns `Ok(None)` if `s` does not refer to a hand joint at all.
+    pub fn parse(s: &str) -> Result<Option<Self>> {
+        let lower = s.to_lowercase();
+        let Some(rest) = lower.strip_prefix("hand:") else {
+            return Ok(None);
+        };
+
+        let (hand, joint) = rest.split_once(':').unwrap_or((rest, "palm"));
+
+        let hand = match hand {
+            "left" => xr::HandEXT::LEFT,
+            "right" => xr::HandEXT::RIGHT,
+            _ => return Err(Error::InvalidHandJoint(s.to_string())),
+        };
+
+        let joint_name = joint.to_uppercase().replace('-', "_");
+        let Some(joint) = (0..xr::HAND_JOINT_COUNT as i32)
+            .map(xr::HandJointEXT::from_raw)
+            .find(|j| format!("{:?}", j) == joint_name)
+        else {
+            return Err(Error::InvalidHandJoint(s.to_string()));
+        };
+
+        Ok(Some(Self { hand, joint }))
+    }
+}
+
+impl fmt::Display for HandJointSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hand = if self.hand == xr::HandEXT::LEFT {
+            "left"
+        } else {
+            "right"
+        };
+        let joint = format!("{:?}", self.joint).to_lowercase().replace('_', "-");
+        write!(f, "hand:{}:{}", hand, joint)
+    }
+}
@@ -0,0 +1,368 @@
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use libmonado as mnd;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, ResultExt};
+use crate::lock::check_offset_unlocked;
+use crate::transformd::TransformD;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// undo warns if the offset moved further than this since the change being undone
+const UNDO_DRIFT_METERS: f64 = 0.001;
+const UNDO_DRIFT_DEG: f64 = 0.1;
+// the next id is taken from the last line, entries are a few hundred bytes
+const TAIL_BYTES: u64 = 4096;
+
+/// An offset that motoc can change. Tracking origins are identified by name,
+/// since their ids are not stable across Monado restarts.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OffsetTarget {
+    Stage,
+    Local,
+    TrackingOrigin(String),
+}
+
+impl fmt::Display for OffsetTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OffsetTarget::Stage => write!(f, "STAGE"),
+            OffsetTarget::Local => write!(f, "LOCAL"),
+            OffsetTarget::TrackingOrigin(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl OffsetTarget {
+    pub fn from_space(space: mnd::ReferenceSpaceType) -> Result<Self> {
+        match space {
+            mnd::ReferenceSpaceType::Stage => Ok(OffsetTarget::Stage),
+            mnd::ReferenceSpaceType::Local => Ok(OffsetTarget::Local),
+            _ => Err(Error::InvalidOperation),
+        }
+    }
+
+    pub fn get(&self, monado: &mnd::Monado) -> Result<TransformD> {
+        match self {
+            OffsetTarget::Stage => Ok(monado
+                .get_reference_space_offset(mnd::ReferenceSpaceType::Stage)?
+                .into()),
+            OffsetTarget::Local => Ok(monado
+                .get_reference_space_offset(mnd::ReferenceSpaceType::Local)?
+                .into()),
+            OffsetTarget::TrackingOrigin(name) => {
+                Ok(self.find_origin(monado, name)?.get_offset()?.into())
+            }
+        }
+    }
+
+    /// Writes the offset without recording it in the journal.
+    fn set(&self, monado: &mnd::Monado, offset: TransformD) -> Result<()> {
+        match self {
+            OffsetTarget::Stage => {
+                monado.set_reference_space_offset(mnd::ReferenceSpaceType::Stage, offset.into())?
+            }
+            OffsetTarget::Local => {
+                monado.set_reference_space_offset(mnd::ReferenceSpaceType::Local, offset.into())?
+            }
+            OffsetTarget::TrackingOrigin(name) => {
+                self.find_origin(monado, name)?.set_offset(offset.into())?
+            }
+        }
+        Ok(())
+    }
+
+    fn find_origin<'m>(
+        &self,
+        monado: &'m mnd::Monado,
+        name: &str,
+    ) -> Result<mnd::TrackingOrigin<'m>> {
+        monado
+            .tracking_origins()?
+            .into_iter()
+            .find(|o| o.name == name)
+            .ok_or_else(|| Error::UnknownTrackingOrigin(name.to_string()))
+    }
+}
+
+/// One offset change, as recorded in the journal.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: u64,
+    /// unix seconds
+    pub time: u64,
+    /// the command that made the change, e.g. `adjust` or `calibrate`
+    pub source: String,
+    pub target: OffsetTarget,
+    pub previous: TransformD,
+    pub new: TransformD,
+    /// set on entries written by `motoc undo`, to the id of the entry they revert
+    #[serde(default)]
+    pub reverts: Option<u64>,
+    /// written when continuous mode starts changing the offset, so the change can be
+    /// undone even if motoc never stops cleanly. `new` is not known yet
+    #[serde(default)]
+    pub ongoing: bool,
+    /// set on the entry written when continuous mode stops, to the id of its ongoing entry
+    #[serde(default)]
+    pub completes: Option<u64>,
+}
+
+impl JournalEntry {
+    fn new(source: &str, target: OffsetTarget, previous: TransformD, new: TransformD) -> Self {
+        Self {
+            id: 0,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            source: source.to_string(),
+            target,
+            previous,
+            new,
+            reverts: None,
+            ongoing: false,
+            completes: None,
+        }
+    }
+}
+
+/// `$XDG_STATE_HOME/motoc`, where the journal is kept.
+pub fn state_dir() -> Result<PathBuf> {
+    let xdg_dirs = xdg::BaseDirectories::new();
+    let mut path = xdg_dirs.get_state_home().ok_or(Error::NoHomeDir)?;
+    path.push("motoc");
+    Ok(path)
+}
+
+fn journal_path() -> Result<PathBuf> {
+    let mut path = state_dir()?;
+    path.push("journal.jsonl");
+    Ok(path)
+}
+
+/// All journal entries, oldest first. Lines that fail to parse are skipped.
+pub fn read_journal() -> Result<Vec<JournalEntry>> {
+    read_entries(&journal_path()?)
+}
+
+fn read_entries(path: &Path) -> Result<Vec<JournalEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => log::warn!("Skipping journal line {}: {}", i + 1, e),
+        }
+    }
+    Ok(entries)
+}
+
+/// The id after the last entry, reading only the end of the journal.
+pub(crate) fn next_id(path: &Path) -> Result<u64> {
+    #[derive(Deserialize)]
+    struct Id {
+        id: u64,
+    }
+
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(1),
+        Err(e) => return Err(e.into()),
+    };
+    let start = file.metadata()?.len().saturating_sub(TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    let tail = String::from_utf8_lossy(&tail);
+    let last = tail.lines().rev().find(|line| !line.trim().is_empty());
+    if let Some(Ok(last)) = last.map(serde_json::from_str::<Id>) {
+        return Ok(last.id + 1);
+    }
+    if last.is_none() && start == 0 {
+        return Ok(1);
+    }
+    // a broken last line, or one longer than the tail
+    Ok(read_entries(path)?
+        .iter()
+        .map(|e| e.id)
+        .max()
+        .map_or(1, |id| id + 1))
+}
+
+fn append(mut entry: JournalEntry) -> Result<u64> {
+    let path = journal_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    entry.id = next_id(&path)?;
+    let id = entry.id;
+    let mut line = serde_json::to_string(&entry)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    Ok(id)
+}
+
+/// Records a change that was already made. A journal that cannot be written
+/// is only warned about, it never stops the change itself.
+pub fn record(source: &str, target: OffsetTarget, previous: TransformD, new: TransformD) {
+    record_entry(JournalEntry::new(source, target, previous, new));
+}
+
+fn record_entry(entry: JournalEntry) -> Option<u64> {
+    match append(entry) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            log::warn!("Could not write to the undo journal: {}", e);
+            None
+        }
+    }
+}
+
+/// Sets a STAGE or LOCAL offset and records the change.
+pub fn set_space_offset(
+    monado: &mnd::Monado,
+    space: mnd::ReferenceSpaceType,
+    offset: TransformD,
+    source: &str,
+) -> Result<()> {
+    let target = OffsetTarget::from_space(space)?;
+    check_offset_unlocked(&target)?;
+    let previous = monado.get_reference_space_offset(space)?.into();
+    monado.set_reference_space_offset(space, offset.into())?;
+    record(source, target, previous, offset);
+    Ok(())
+}
+
+/// Sets a tracking origin offset and records the change.
+pub fn set_origin_offset(
+    origin: &mnd::TrackingOrigin,
+    offset: TransformD,
+    source: &str,
+) -> Result<()> {
+    let target = OffsetTarget::TrackingOrigin(origin.name.clone());
+    check_offset_unlocked(&target)?;
+    let previous = origin.get_offset()?.into();
+    origin.set_offset(offset.into())?;
+    record(source, target, previous, offset);
+    Ok(())
+}
+
+/// Journals a run of writes to one offset as a single change, e.g. for calibrators
+/// that nudge an offset every frame. Call `before_write` before each write.
+///
+/// The starting offset is recorded with the first write, so the change can be undone
+/// even if `finish` never runs, e.g. after a crash or when Monado went away.
+pub(crate) struct ContinuousChange {
+    source: &'static str,
+    // target, starting offset and the id of the ongoing entry
+    start: Option<(OffsetTarget, TransformD, Option<u64>)>,
+}
+
+impl ContinuousChange {
+    pub(crate) fn new(source: &'static str) -> Self {
+        Self {
+            source,
+            start: None,
+        }
+    }
+
+    pub(crate) fn before_write(&mut self, target: OffsetTarget, current: TransformD) {
+        if self.start.is_none() {
+            let mut entry = JournalEntry::new(self.source, target.clone(), current, current);
+            entry.ongoing = true;
+            self.start = Some((target, current, record_entry(entry)));
+        }
+    }
+
+    /// records the change from the first write until now
+    pub(crate) fn finish(&mut self, monado: &mnd::Monado) {
+        let Some((target, previous, ongoing)) = self.start.take() else {
+            return;
+        };
+        match target.get(monado) {
+            Ok(new) => {
+                let mut entry = JournalEntry::new(self.source, target, previous, new);
+                entry.completes = ongoing;
+                record_entry(entry);
+            }
+            Err(e) => log::warn!("Could not journal the change to {}: {}", target, e),
+        }
+    }
+}
+
+/// Whether the entry is the start of a continuous change that stopped cleanly.
+/// The entry that completes it stands for the whole change.
+pub fn is_completed(entries: &[JournalEntry], entry: &JournalEntry) -> bool {
+    entry.ongoing && entries.iter().any(|c| c.completes == Some(entry.id))
+}
+
+/// Entries that can still be undone, most recent first.
+/// Undo entries themselves, entries that were already undone and completed
+/// continuous changes are left out.
+pub fn undoable(entries: &[JournalEntry]) -> Vec<&JournalEntry> {
+    entries
+        .iter()
+        .rev()
+        .filter(|e| e.reverts.is_none())
+        .filter(|e| !is_completed(entries, e))
+        .filter(|e| !entries.iter().any(|u| u.reverts == Some(e.id)))
+        .collect()
+}
+
+/// Reverts the last `count` changes, most recent first. Returns the reverted entries.
+pub fn undo(monado: &mnd::Monado, count: usize) -> Result<Vec<JournalEntry>> {
+    let entries = read_journal()?;
+    let todo: Vec<JournalEntry> = undoable(&entries)
+        .into_iter()
+        .take(count)
+        .cloned()
+        .collect();
+
+    for entry in todo.iter() {
+        check_offset_unlocked(&entry.target)?;
+        let current = entry
+            .target
+            .get(monado)
+            .context(format!("Unable to get offset of {}", entry.target))?;
+
+        // where an interrupted continuous change ended is unknown
+        if !entry.ongoing
+            && ((current.origin - entry.new.origin).norm() > UNDO_DRIFT_METERS
+                || current.basis.angle_to(&entry.new.basis).to_degrees() > UNDO_DRIFT_DEG)
+        {
+            log::warn!(
+                "{} was changed again since #{}, undoing anyway.",
+                entry.target,
+                entry.id
+            );
+        }
+
+        entry
+            .target
+            .set(monado, entry.previous)
+            .context(format!("Unable to set offset of {}", entry.target))?;
+        let mut undo = JournalEntry::new("undo", entry.target.clone(), current, entry.previous);
+        undo.reverts = Some(entry.id);
+        append(undo)?;
+    }
+
+    Ok(todo)
+}
@@ -1,16 +1,49 @@
+mod aliases;
 mod calibrator;
+mod clients;
 mod common;
+mod config;
+mod daemon;
+#[cfg(feature = "dbus")]
+mod dbus;
 mod error;
 mod helpers_xr;
+mod journal;
+mod lock;
+mod metrics;
+mod monado_config;
+mod osc;
+mod presets;
+mod profiles;
+mod service;
+mod snapshot;
+mod svc;
+mod telemetry;
 mod transformd;
 
 #[cfg(test)]
 mod test;
 
+pub use aliases::*;
 pub use calibrator::*;
 pub use common::*;
+pub use config::*;
+pub use daemon::*;
+#[cfg(feature = "dbus")]
+pub use dbus::*;
 pub use error::{Error, ResultExt};
 pub use helpers_xr::*;
+pub use journal::*;
+pub use lock::*;
+pub use metrics::*;
+pub use monado_config::*;
+pub use osc::*;
+pub use presets::*;
+pub use profiles::*;
+pub use service::*;
+pub use snapshot::*;
+pub use svc::*;
+pub use telemetry::*;
 pub use transformd::*;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,115 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{LazyLock, Mutex},
+};
+
+use crate::error::Error;
+use crate::journal::OffsetTarget;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// targets locked by this process, which may keep writing to them
+static HELD: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Ownership of an offset that this process keeps changing, e.g. while maintaining it.
+///
+/// Other motoc processes refuse to write to the offset until this is dropped.
+/// The lock is an advisory file lock in `$XDG_RUNTIME_DIR/motoc`, so it is
+/// released by the OS even if the owner crashes.
+pub struct OffsetLock {
+    key: String,
+    _file: Option<File>,
+}
+
+impl Drop for OffsetLock {
+    fn drop(&mut self) {
+        if let Ok(mut held) = HELD.lock() {
+            held.remove(&self.key);
+        }
+    }
+}
+
+fn lock_key(target: &OffsetTarget) -> String {
+    let name = match target {
+        OffsetTarget::Stage => String::from("stage"),
+        OffsetTarget::Local => String::from("local"),
+        OffsetTarget::TrackingOrigin(name) => format!(
+            "origin-{}",
+            name.chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>()
+        ),
+    };
+    format!("{}.lock", name)
+}
+
+fn lock_path(key: &str) -> Option<PathBuf> {
+    let xdg_dirs = xdg::BaseDirectories::new();
+    let mut path = xdg_dirs.get_runtime_directory().ok()?.clone();
+    path.push("motoc");
+    path.push(key);
+    Some(path)
+}
+
+/// Opens and locks the lock file, or returns who holds it.
+fn try_lock(target: &OffsetTarget, key: &str) -> Result<Option<File>> {
+    let Some(path) = lock_path(key) else {
+        log::debug!("No XDG_RUNTIME_DIR, not coordinating with other motoc processes.");
+        return Ok(None);
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?;
+
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) => {
+            let mut owner = String::new();
+            let _ = file.read_to_string(&mut owner);
+            Err(Error::OffsetLocked {
+                target: target.to_string(),
+                owner: owner.trim().to_string(),
+            })
+        }
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// Takes ownership of the offset for as long as the returned lock is alive.
+/// `source` is shown to other processes that try to write to it.
+pub fn lock_offset(target: &OffsetTarget, source: &str) -> Result<OffsetLock> {
+    let key = lock_key(target);
+    let mut file = try_lock(target, &key)?;
+
+    if let Some(file) = file.as_mut() {
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "motoc {} (pid {})", source, std::process::id())?;
+    }
+
+    if let Ok(mut held) = HELD.lock() {
+        held.insert(key.clone());
+    }
+
+    Ok(OffsetLock { key, _file: file })
+}
+
+/// Fails if another motoc process currently owns the offset.
+pub fn check_offset_unlocked(target: &OffsetTarget) -> Result<()> {
+    let key = lock_key(target);
+    if HELD.lock().is_ok_and(|held| held.contains(&key)) {
+        return Ok(());
+    }
+    // dropping the file releases the lock right away
+    try_lock(target, &key).map(|_| ())
+}
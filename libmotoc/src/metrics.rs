@@ -0,0 +1,289 @@
+use std::{
+    fmt::Write as _,
+    io::Write,
+    net::{SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+use libmonado::DeviceLogic;
+use nalgebra::Vector3;
+use openxr as xr;
+
+use crate::clients::Clients;
+use crate::common::CalibratorData;
+use crate::error::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub const DEFAULT_METRICS_ADDR: &str = "127.0.0.1:9464";
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+// requests longer than this are refused, a scrape is a few hundred bytes
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+// scrapers send their request right away
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Builds an exposition in the OpenMetrics text format, which Prometheus also reads.
+#[derive(Default)]
+pub struct MetricsText {
+    out: String,
+}
+
+impl MetricsText {
+    /// Starts a metric family. `kind` is `gauge` or `counter`.
+    pub fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+    }
+
+    /// Adds a sample. Counter samples are named with a `_total` suffix.
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+                .collect();
+            let _ = write!(self.out, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.out, " {}", value);
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push_str("# EOF\n");
+        self.out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn norm(v: xr::Vector3f) -> f64 {
+    Vector3::new(v.x, v.y, v.z).norm() as f64
+}
+
+/// The telemetry of continuous mode and the state of the connected devices.
+pub fn render_metrics(data: &CalibratorData) -> String {
+    let t = &data.telemetry;
+    let mut m = MetricsText::default();
+
+    if let Some(deviation) = t.deviation {
+        m.family(
+            "motoc_continuous_deviation_meters",
+            "gauge",
+            "Distance of continuous mode from the target offset.",
+        );
+        m.sample("motoc_continuous_deviation_meters", &[], deviation.meters);
+        m.family(
+            "motoc_continuous_deviation_degrees",
+            "gauge",
+            "Angle of continuous mode from the target offset.",
+        );
+        m.sample("motoc_continuous_deviation_degrees", &[], deviation.degrees);
+    }
+
+    m.family(
+        "motoc_corrections",
+        "counter",
+        "Offset updates made by continuous mode.",
+    );
+    m.sample("motoc_corrections_total", &[], t.corrections as f64);
+    m.family(
+        "motoc_correction_meters",
+        "counter",
+        "Sum of the offset updates made by continuous mode.",
+    );
+    m.sample("motoc_correction_meters_total", &[], t.correction_meters);
+    m.family(
+        "motoc_correction_degrees",
+        "counter",
+        "Sum of the offset rotations made by continuous mode.",
+    );
+    m.sample("motoc_correction_degrees_total", &[], t.correction_degrees);
+    m.family(
+        "motoc_anomalies",
+        "counter",
+        "Times continuous mode saw an offset too far from the center.",
+    );
+    m.sample("motoc_anomalies_total", &[], t.anomalies as f64);
+    m.family(
+        "motoc_anomaly_resets",
+        "counter",
+        "Times an anomaly reset the offset.",
+    );
+    m.sample("motoc_anomaly_resets_total", &[], t.anomaly_resets as f64);
+
+    m.family(
+        "motoc_tracking_losses",
+        "counter",
+        "Times a device stopped tracking.",
+    );
+    for (serial, losses) in t.tracking_losses.iter() {
+        m.sample(
+            "motoc_tracking_losses_total",
+            &[("serial", serial)],
+            *losses as f64,
+        );
+    }
+
+    let devices: Vec<_> = data.connected_devices().map(|(_, d)| d).collect();
+    m.family(
+        "motoc_device_tracked",
+        "gauge",
+        "Whether the device is tracking.",
+    );
+    for device in devices.iter() {
+        let tracked = t.tracked.get(&device.serial).copied().unwrap_or(false);
+        m.sample(
+            "motoc_device_tracked",
+            &[("serial", &device.serial)],
+            f64::from(u8::from(tracked)),
+        );
+    }
+
+    let velocities: Vec<_> = devices
+        .iter()
+        .filter_map(|d| Some((d, d.space.relate(&data.stage, data.now).ok()?.1)))
+        .collect();
+    m.family(
+        "motoc_device_linear_speed_meters_per_second",
+        "gauge",
+        "How fast the device moves.",
+    );
+    for (device, velocity) in velocities.iter() {
+        if velocity
+            .velocity_flags
+            .contains(xr::SpaceVelocityFlags::LINEAR_VALID)
+        {
+            m.sample(
+                "motoc_device_linear_speed_meters_per_second",
+                &[("serial", &device.serial)],
+                norm(velocity.linear_velocity),
+            );
+        }
+    }
+    m.family(
+        "motoc_device_angular_speed_radians_per_second",
+        "gauge",
+        "How fast the device turns.",
+    );
+    for (device, velocity) in velocities.iter() {
+        if velocity
+            .velocity_flags
+            .contains(xr::SpaceVelocityFlags::ANGULAR_VALID)
+        {
+            m.sample(
+                "motoc_device_angular_speed_radians_per_second",
+                &[("serial", &device.serial)],
+                norm(velocity.angular_velocity),
+            );
+        }
+    }
+
+    let batteries: Vec<_> = devices
+        .iter()
+        .filter_map(|d| Some((d, d.inner.battery_status().ok()?)))
+        .filter(|(_, battery)| battery.present)
+        .collect();
+    m.family(
+        "motoc_device_battery_ratio",
+        "gauge",
+        "Battery charge of the device, from 0 to 1.",
+    );
+    for (device, battery) in batteries.iter() {
+        m.sample(
+            "motoc_device_battery_ratio",
+            &[("serial", &device.serial)],
+            battery.charge as f64,
+        );
+    }
+    m.family(
+        "motoc_device_charging",
+        "gauge",
+        "Whether the device is charging.",
+    );
+    for (device, battery) in batteries.iter() {
+        m.sample(
+            "motoc_device_charging",
+            &[("serial", &device.serial)],
+            f64::from(u8::from(battery.charging)),
+        );
+    }
+
+    m.finish()
+}
+
+/// A minimal HTTP endpoint for scraping, polled from the main loop.
+pub struct MetricsServer {
+    clients: Clients<TcpListener>,
+}
+
+impl MetricsServer {
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        log::info!(
+            "Serving metrics on http://{}/metrics",
+            listener.local_addr()?
+        );
+        Ok(Self {
+            clients: Clients::new(listener, IDLE_TIMEOUT, "Metrics"),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.clients.listener().local_addr()?)
+    }
+
+    /// Accepts new clients and answers the requests that are complete.
+    /// `render` is only called if someone asks for the metrics.
+    pub fn poll(&mut self, render: impl FnOnce() -> String) {
+        let mut body = None;
+        let mut render = Some(render);
+        self.clients.poll(|conn| {
+            let Some(end) = conn.buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+                if conn.buf.len() > MAX_REQUEST_BYTES {
+                    respond(&conn.stream, "431 Request Header Fields Too Large", "");
+                    return false;
+                }
+                return true;
+            };
+
+            let head = String::from_utf8_lossy(&conn.buf[..end]);
+            let mut request = head.lines().next().unwrap_or_default().split(' ');
+            match (request.next(), request.next()) {
+                (Some("GET"), Some("/metrics" | "/")) => {
+                    let body = body.get_or_insert_with(|| render.take().map(|r| r()).unwrap());
+                    respond(&conn.stream, "200 OK", body);
+                }
+                (Some("GET"), _) => respond(&conn.stream, "404 Not Found", ""),
+                _ => respond(&conn.stream, "405 Method Not Allowed", ""),
+            }
+            false
+        });
+    }
+}
+
+fn respond(stream: &TcpStream, status: &str, body: &str) {
+    let mut stream = stream;
+    let answer = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        CONTENT_TYPE,
+        body.len(),
+        body
+    );
+    // the answer may not fit the socket buffer at once
+    let sent = stream
+        .set_nonblocking(false)
+        .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+        .and_then(|_| stream.write_all(answer.as_bytes()));
+    if let Err(e) = sent {
+        log::debug!("Could not answer metrics client: {}", e);
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use nalgebra::UnitQuaternion;
+use serde_json::{json, Value};
+
+use crate::common::{OffsetType, SavedCalibration};
+use crate::error::Error;
+use crate::profiles::write_atomic;
+use crate::transformd::TransformD;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// the pose input Monado reads from the tracker when none is given
+const DEFAULT_INPUT_NAME: &str = "XRT_INPUT_GENERIC_TRACKER_POSE";
+
+/// `$XDG_CONFIG_HOME/monado/config_v0.json`
+pub fn monado_config_path() -> Result<PathBuf> {
+    let xdg_dirs = xdg::BaseDirectories::new();
+    let mut path = xdg_dirs.get_config_home().ok_or(Error::NoHomeDir)?;
+    path.push("monado");
+    path.push("config_v0.json");
+    Ok(path)
+}
+
+fn pose_json(pose: &TransformD) -> Value {
+    let q = UnitQuaternion::from_rotation_matrix(&pose.basis);
+    json!({
+        "orientation": { "x": q.i, "y": q.j, "z": q.k, "w": q.w },
+        "position": { "x": pose.origin.x, "y": pose.origin.y, "z": pose.origin.z },
+    })
+}
+
+/// A Monado tracking override built from a device offset. This is not what
+/// continuous mode does, Monado's config cannot store a tracking origin offset.
+///
+/// Continuous mode moves the whole tracking origin of the tracker (`dst`) so
+/// that it follows the target (`src`, usu. HMD). A `direct` override instead
+/// makes Monado report the target at the tracker's pose times the inverse of
+/// the override offset: the target stops using its own tracking, and the other
+/// devices of its tracking origin are not moved with it. Tracking origin
+/// offsets have no override at all, so `None` is returned for those.
+pub fn tracking_override(saved: &SavedCalibration) -> Option<Value> {
+    if saved.offset_type != OffsetType::Device {
+        return None;
+    }
+
+    Some(json!({
+        "type": "direct",
+        "target_device_serial": saved.src,
+        "tracker_device_serial": saved.dst,
+        "xrt_input_name": DEFAULT_INPUT_NAME,
+        "offset": pose_json(&saved.offset.inverse()),
+    }))
+}
+
+/// Adds overrides to `tracking.tracking_overrides` of a Monado config,
+/// replacing any existing override for the same pair of devices.
+pub fn merge_tracking_overrides(config: &mut Value, overrides: &[Value]) -> Result<()> {
+    let not_an_object = || Error::InvalidMonadoConfig(String::from("expected a JSON object"));
+
+    let root = config.as_object_mut().ok_or_else(not_an_object)?;
+    let tracking = root
+        .entry("tracking")
+        .or_insert_with(|| json!({ "version": 0 }))
+        .as_object_mut()
+        .ok_or_else(not_an_object)?;
+    let list = tracking
+        .entry("tracking_overrides")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+        .ok_or_else(|| Error::InvalidMonadoConfig(String::from("expected a list of overrides")))?;
+
+    let pair = |v: &Value| {
+        (
+            v.get("target_device_serial").cloned(),
+            v.get("tracker_device_serial").cloned(),
+        )
+    };
+    for o in overrides.iter() {
+        list.retain(|existing| pair(existing) != pair(o));
+        list.push(o.clone());
+    }
+    Ok(())
+}
+
+/// Reads a Monado config, or starts an empty one if the file does not exist.
+pub fn read_monado_config(path: &Path) -> Result<Value> {
+    if !path.exists() {
+        return Ok(json!({}));
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn format_monado_config(config: &Value) -> Result<String> {
+    Ok(serde_json::to_string_pretty(config)?)
+}
+
+pub fn write_monado_config(path: &Path, config: &Value) -> Result<()> {
+    write_atomic(path, &format_monado_config(config)?)
+}
@@ -0,0 +1,189 @@
+use std::{
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use crate::calibrator::CalibratorStatus;
+use crate::error::Error;
+use crate::telemetry::Telemetry;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The port VRChat and most OSC overlays listen on.
+pub const DEFAULT_OSC_TARGET: &str = "127.0.0.1:9000";
+
+// continuous mode updates every frame, receivers don't need that many
+const DEVIATION_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+}
+
+fn push_padded(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    // at least one NUL, then up to a multiple of 4
+    buf.push(0);
+    while !buf.len().is_multiple_of(4) {
+        buf.push(0);
+    }
+}
+
+/// An OSC 1.0 message, ready to go into one UDP packet.
+pub fn encode_osc(address: &str, args: &[OscArg]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    push_padded(&mut buf, address);
+
+    let tags: String = std::iter::once(',')
+        .chain(args.iter().map(|arg| match arg {
+            OscArg::Int(_) => 'i',
+            OscArg::Float(_) => 'f',
+            OscArg::Str(_) => 's',
+        }))
+        .collect();
+    push_padded(&mut buf, &tags);
+
+    for arg in args {
+        match arg {
+            OscArg::Int(v) => buf.extend_from_slice(&v.to_be_bytes()),
+            OscArg::Float(v) => buf.extend_from_slice(&v.to_be_bytes()),
+            OscArg::Str(v) => push_padded(&mut buf, v),
+        }
+    }
+    buf
+}
+
+/// Sends OSC messages over UDP. Nobody may be listening, so sending never fails.
+pub struct OscSender {
+    socket: UdpSocket,
+    target: SocketAddr,
+}
+
+impl OscSender {
+    /// `target` is `HOST:PORT`.
+    pub fn connect(target: &str) -> Result<Self> {
+        let target = target
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::UnknownHost(target.to_string()))?;
+        let local: SocketAddr = if target.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+        log::info!("Sending OSC to {}", target);
+        Ok(Self { socket, target })
+    }
+
+    pub fn target(&self) -> SocketAddr {
+        self.target
+    }
+
+    pub fn send(&self, address: &str, args: &[OscArg]) {
+        if let Err(e) = self.socket.send_to(&encode_osc(address, args), self.target) {
+            log::debug!("Could not send OSC {}: {}", address, e);
+        }
+    }
+}
+
+/// Reports calibration state over OSC as it changes:
+///
+/// - `/motoc/calibration/started`
+/// - `/motoc/calibration/progress` (float 0..1, string message)
+/// - `/motoc/calibration/finished`
+/// - `/motoc/continuous/deviation` (float meters, float degrees)
+/// - `/motoc/anomaly` (int 1 when one starts, 0 when it is over)
+pub struct OscOutput {
+    sender: OscSender,
+    calibrating: bool,
+    progress: Option<(u64, u64)>,
+    anomaly: bool,
+    last_deviation: Option<Instant>,
+}
+
+impl OscOutput {
+    pub fn new(sender: OscSender) -> Self {
+        Self {
+            sender,
+            calibrating: false,
+            progress: None,
+            anomaly: false,
+            last_deviation: None,
+        }
+    }
+
+    /// Called every frame. `running` is whether a calibrator is active.
+    pub fn step(
+        &mut self,
+        telemetry: &Telemetry,
+        running: bool,
+        status: Option<&CalibratorStatus>,
+    ) {
+        // continuous mode keeps running, it is not a calibration in progress
+        let calibrating = running && !telemetry.continuous;
+        if calibrating != self.calibrating {
+            self.calibrating = calibrating;
+            self.progress = None;
+            let address = if calibrating {
+                "/motoc/calibration/started"
+            } else {
+                "/motoc/calibration/finished"
+            };
+            self.sender.send(address, &[]);
+        }
+
+        if let (
+            true,
+            Some(CalibratorStatus::Progress {
+                current,
+                max,
+                message,
+            }),
+        ) = (calibrating, status)
+        {
+            if self.progress != Some((*current, *max)) {
+                self.progress = Some((*current, *max));
+                let ratio = if *max > 0 {
+                    *current as f32 / *max as f32
+                } else {
+                    0.0
+                };
+                self.sender.send(
+                    "/motoc/calibration/progress",
+                    &[OscArg::Float(ratio), OscArg::Str(message.clone())],
+                );
+            }
+        }
+
+        if let (true, Some(deviation)) = (telemetry.continuous, telemetry.deviation) {
+            if self
+                .last_deviation
+                .is_none_or(|t| t.elapsed() >= DEVIATION_INTERVAL)
+            {
+                self.last_deviation = Some(Instant::now());
+                self.sender.send(
+                    "/motoc/continuous/deviation",
+                    &[
+                        OscArg::Float(deviation.meters as f32),
+                        OscArg::Float(deviation.degrees as f32),
+                    ],
+                );
+            }
+        }
+
+        if telemetry.anomaly != self.anomaly {
+            self.anomaly = telemetry.anomaly;
+            self.sender
+                .send("/motoc/anomaly", &[OscArg::Int(i32::from(self.anomaly))]);
+        }
+    }
+
+    /// The calibrator went away without a last step, e.g. on shutdown or when Monado stopped.
+    pub fn stopped(&mut self) {
+        self.step(&Telemetry::default(), false, None);
+    }
+}
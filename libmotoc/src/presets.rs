@@ -0,0 +1,116 @@
+use std::fs::File;
+
+use nalgebra::{Rotation3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::profiles::config_dir;
+use crate::transformd::TransformD;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A named device-to-device offset for a known way of mounting a tracker.
+///
+/// The offset uses the same conventions as `motoc offset`: it is the pose of the source
+/// device (usu. HMD) as seen from the destination device (usu. tracker), in meters and degrees.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MountPreset {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub x: f64,
+    #[serde(default)]
+    pub y: f64,
+    #[serde(default)]
+    pub z: f64,
+    #[serde(default)]
+    pub yaw: f64,
+    #[serde(default)]
+    pub pitch: f64,
+    #[serde(default)]
+    pub roll: f64,
+    /// false for entries loaded from the user's presets.json
+    #[serde(skip)]
+    pub builtin: bool,
+}
+
+impl MountPreset {
+    pub fn offset(&self) -> TransformD {
+        TransformD {
+            origin: Vector3::new(self.x, self.y, self.z),
+            basis: Rotation3::from_euler_angles(
+                self.roll.to_radians(),
+                self.pitch.to_radians(),
+                self.yaw.to_radians(),
+            ),
+        }
+    }
+}
+
+fn builtin(name: &str, description: &str, pos: [f64; 3]) -> MountPreset {
+    MountPreset {
+        name: name.into(),
+        description: description.into(),
+        x: pos[0],
+        y: pos[1],
+        z: pos[2],
+        yaw: 0.0,
+        pitch: 0.0,
+        roll: 0.0,
+        builtin: true,
+    }
+}
+
+/// Starting points for the common brackets that hold an upright, forward-facing tracker.
+///
+/// The HMD pose sits between the eyes. The distances come from where each headset's strap
+/// puts the bracket on a median adult head: the crown about 11 cm above and 9 cm behind the
+/// eyes, the back of the head about 19 cm behind them. They are not measured on a bracket,
+/// so refine them, e.g. with `motoc offset --preset NAME --refine`.
+pub fn builtin_presets() -> Vec<MountPreset> {
+    vec![
+        builtin(
+            "quest3-strap-top",
+            "Quest 3: tracker on the top strap, facing forward (estimate, refine it)",
+            [0.0, -0.11, -0.09],
+        ),
+        builtin(
+            "pico4-battery-top",
+            "Pico 4: tracker on top of the rear battery pack, facing forward (estimate, refine it)",
+            [0.0, -0.04, -0.21],
+        ),
+    ]
+}
+
+/// Built-in presets followed by the user's own from `$XDG_CONFIG_HOME/motoc/presets.json`.
+/// User presets replace built-in ones of the same name.
+pub fn load_presets() -> Result<Vec<MountPreset>> {
+    let mut presets = builtin_presets();
+
+    let mut path = config_dir()?;
+    path.push("presets.json");
+
+    if !path.exists() {
+        return Ok(presets);
+    }
+
+    log::debug!("Will load mount presets from: {}", path.to_string_lossy());
+
+    let f = File::open(path)?;
+    let user: Vec<MountPreset> = serde_json::from_reader(f)?;
+
+    for preset in user {
+        presets.retain(|p| !p.name.eq_ignore_ascii_case(&preset.name));
+        presets.push(preset);
+    }
+
+    Ok(presets)
+}
+
+pub fn find_preset(name: &str) -> Result<MountPreset> {
+    load_presets()?
+        .into_iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| Error::PresetNotFound(name.to_string()))
+}
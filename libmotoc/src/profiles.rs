@@ -0,0 +1,451 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use nalgebra::{Quaternion, Rotation3, UnitQuaternion, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::aliases::{Aliases, NameKind};
+use crate::common::{CalibrationMeta, OffsetType, SavedCalibration};
+use crate::error::Error;
+use crate::transformd::TransformD;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Version of the profile format written by this build.
+/// Version 1 was a bare `SavedCalibration` with the rotation as a raw matrix.
+pub const PROFILE_VERSION: u32 = 2;
+
+// euler angles that disagree with the quaternion by more than this were edited by hand
+const EULER_EDIT_EPSILON_DEG: f64 = 0.01;
+
+/// A saved calibration profile, holding up to one offset per destination origin.
+#[derive(Default)]
+pub struct Profile {
+    pub offsets: Vec<SavedCalibration>,
+}
+
+impl Profile {
+    /// Parses a profile of any known version, migrating older ones.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+
+        let Some(version) = value.get("version") else {
+            log::debug!("Migrating a version 1 profile.");
+            let legacy: SavedCalibration = serde_json::from_value(value)?;
+            return Ok(Self {
+                offsets: vec![legacy],
+            });
+        };
+
+        let version = version.as_u64().unwrap_or(u64::MAX);
+        if version > PROFILE_VERSION as u64 {
+            return Err(Error::UnsupportedProfileVersion(version));
+        }
+
+        let file: ProfileFile = serde_json::from_value(value)?;
+        Ok(Self {
+            offsets: file
+                .offsets
+                .into_iter()
+                .map(SavedCalibration::try_from)
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        let file = ProfileFile {
+            version: PROFILE_VERSION,
+            offsets: self.offsets.iter().map(OffsetEntry::from).collect(),
+        };
+        Ok(serde_json::to_string_pretty(&file)?)
+    }
+
+    /// Adds an offset, replacing any existing one for the same destination.
+    pub fn upsert(&mut self, saved: SavedCalibration) {
+        self.offsets.retain(|o| {
+            let same_origin =
+                o.meta.dst_origin.is_some() && o.meta.dst_origin == saved.meta.dst_origin;
+            o.dst != saved.dst && !same_origin
+        });
+        self.offsets.push(saved);
+    }
+
+    /// Renames an origin or device serial in every offset. Returns how many offsets changed.
+    pub fn rebind(&mut self, from: &str, to: &str) -> usize {
+        let mut changed = 0;
+        for saved in self.offsets.iter_mut() {
+            let mut hit = false;
+            for name in [&mut saved.src, &mut saved.dst] {
+                if name == from {
+                    *name = to.to_string();
+                    hit = true;
+                }
+            }
+            let meta = &mut saved.meta;
+            for name in [&mut meta.src_origin, &mut meta.dst_origin]
+                .into_iter()
+                .flatten()
+            {
+                if name == from {
+                    *name = to.to_string();
+                    hit = true;
+                }
+            }
+            for dev in [&mut meta.src_device, &mut meta.dst_device]
+                .into_iter()
+                .flatten()
+            {
+                if dev.serial == from {
+                    dev.serial = to.to_string();
+                    hit = true;
+                }
+            }
+            changed += usize::from(hit);
+        }
+        changed
+    }
+}
+
+/// How well a profile fits the devices and tracking origins that are present.
+pub struct ProfileMatch {
+    /// higher is better. offsets that cannot be applied count against it
+    pub score: i32,
+    /// number of offsets in the profile that can be applied
+    pub usable: usize,
+    /// what was found and what was missing, for explaining the choice
+    pub reasons: Vec<String>,
+}
+
+impl Profile {
+    /// Rates the profile against connected device serials and tracking origin names.
+    /// Aliases count as present, similar names do not.
+    pub fn match_against(
+        &self,
+        serials: &[&str],
+        origins: &[&str],
+        aliases: &Aliases,
+    ) -> ProfileMatch {
+        let mut fit = ProfileMatch {
+            score: 0,
+            usable: 0,
+            reasons: Vec::new(),
+        };
+
+        for saved in self.offsets.iter() {
+            let (present, kind) = match saved.offset_type {
+                OffsetType::Device => (serials, NameKind::Device),
+                OffsetType::TrackingOrigin => (origins, NameKind::Origin),
+            };
+
+            let missing: Vec<&str> = [saved.src.as_str(), saved.dst.as_str()]
+                .into_iter()
+                .filter(|name| !present.iter().any(|p| aliases.same(kind, p, name)))
+                .collect();
+
+            // a tracking origin offset still applies without its source, against identity
+            let usable = match saved.offset_type {
+                OffsetType::Device => missing.is_empty(),
+                OffsetType::TrackingOrigin => !missing.contains(&saved.dst.as_str()),
+            };
+            if !usable {
+                fit.score -= 2;
+                fit.reasons.push(format!(
+                    "{} {} is missing",
+                    kind.label(),
+                    missing.join(" and ")
+                ));
+                continue;
+            }
+
+            fit.usable += 1;
+            fit.score += 4 - 2 * missing.len() as i32;
+            fit.reasons.push(format!(
+                "{} {} → {} present",
+                kind.label(),
+                saved.src,
+                saved.dst
+            ));
+
+            // devices used for an origin calibration hint at the same setup
+            for dev in [&saved.meta.src_device, &saved.meta.dst_device]
+                .into_iter()
+                .flatten()
+            {
+                if saved.offset_type == OffsetType::TrackingOrigin
+                    && serials
+                        .iter()
+                        .any(|s| aliases.same(NameKind::Device, s, &dev.serial))
+                {
+                    fit.score += 1;
+                    fit.reasons.push(format!(
+                        "calibrated with {}, which is connected",
+                        dev.serial
+                    ));
+                }
+            }
+        }
+
+        fit
+    }
+
+    /// unix time of the newest offset in the profile, 0 if unknown
+    pub fn created(&self) -> u64 {
+        self.offsets
+            .iter()
+            .map(|o| o.meta.created)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProfileFile {
+    version: u32,
+    offsets: Vec<OffsetEntry>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct EulerDegrees {
+    yaw: f64,
+    pitch: f64,
+    roll: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OffsetEntry {
+    offset_type: OffsetType,
+    src: String,
+    dst: String,
+    /// meters
+    position: [f64; 3],
+    /// unit quaternion as [x, y, z, w]
+    #[serde(default)]
+    rotation: Option<[f64; 4]>,
+    /// same rotation in degrees, for editing by hand. must agree with `rotation` if both are set
+    #[serde(default)]
+    euler: Option<EulerDegrees>,
+    #[serde(default)]
+    meta: CalibrationMeta,
+}
+
+impl From<&SavedCalibration> for OffsetEntry {
+    fn from(saved: &SavedCalibration) -> Self {
+        let q = UnitQuaternion::from_rotation_matrix(&saved.offset.basis);
+        let (roll, pitch, yaw) = saved.offset.basis.euler_angles();
+        Self {
+            offset_type: saved.offset_type,
+            src: saved.src.clone(),
+            dst: saved.dst.clone(),
+            position: saved.offset.origin.into(),
+            rotation: Some([q.i, q.j, q.k, q.w]),
+            euler: Some(EulerDegrees {
+                yaw: yaw.to_degrees(),
+                pitch: pitch.to_degrees(),
+                roll: roll.to_degrees(),
+            }),
+            meta: saved.meta.clone(),
+        }
+    }
+}
+
+impl TryFrom<OffsetEntry> for SavedCalibration {
+    type Error = Error;
+
+    fn try_from(entry: OffsetEntry) -> Result<Self> {
+        let from_quat = entry.rotation.map(|[x, y, z, w]| {
+            UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z)).to_rotation_matrix()
+        });
+        let from_euler = entry.euler.map(|e| {
+            Rotation3::from_euler_angles(
+                e.roll.to_radians(),
+                e.pitch.to_radians(),
+                e.yaw.to_radians(),
+            )
+        });
+
+        let basis = match (from_quat, from_euler) {
+            (Some(q), Some(e)) => {
+                let apart = q.angle_to(&e).to_degrees();
+                if apart > EULER_EDIT_EPSILON_DEG {
+                    // one of them was edited by hand, and there is no telling which
+                    let (rotation, euler) = (entry.rotation.unwrap(), entry.euler.unwrap());
+                    return Err(Error::ConflictingRotation(format!(
+                        "offset for {}: rotation [{}, {}, {}, {}] and euler yaw {} pitch {} roll {} \
+                         are {:.2}° apart, remove the one that is wrong",
+                        entry.dst,
+                        rotation[0],
+                        rotation[1],
+                        rotation[2],
+                        rotation[3],
+                        euler.yaw,
+                        euler.pitch,
+                        euler.roll,
+                        apart
+                    )));
+                }
+                q
+            }
+            (Some(q), None) => q,
+            (None, Some(e)) => e,
+            (None, None) => Rotation3::identity(),
+        };
+
+        Ok(Self {
+            offset_type: entry.offset_type,
+            src: entry.src,
+            dst: entry.dst,
+            offset: TransformD {
+                origin: Vector3::from(entry.position),
+                basis,
+            },
+            meta: entry.meta,
+        })
+    }
+}
+
+// files in the config dir that are not calibration profiles
+const RESERVED_NAMES: [&str; 2] = ["presets", "aliases"];
+
+/// `$XDG_CONFIG_HOME/motoc`, where profiles and other settings are kept.
+pub fn config_dir() -> Result<PathBuf> {
+    let xdg_dirs = xdg::BaseDirectories::new();
+    let mut path = xdg_dirs.get_config_home().ok_or(Error::NoHomeDir)?;
+    path.push("motoc");
+    Ok(path)
+}
+
+/// Whether the name can be used as a file name in the config dir.
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !(name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']))
+}
+
+pub fn profile_path(name: &str) -> Result<PathBuf> {
+    if !is_valid_name(name) || RESERVED_NAMES.contains(&name) {
+        return Err(Error::InvalidProfileName(name.to_string()));
+    }
+
+    let mut path = config_dir()?;
+    path.push(format!("{}.json", name));
+    Ok(path)
+}
+
+pub fn read_profile(name: &str) -> Result<Profile> {
+    let path = profile_path(name)?;
+    if !path.exists() {
+        return Err(Error::ProfileNotFound(name.to_string()));
+    }
+
+    log::debug!(
+        "Will load calibration data from: {}",
+        path.to_string_lossy()
+    );
+
+    Profile::from_json(&fs::read_to_string(path)?)
+}
+
+pub fn write_profile(name: &str, profile: &Profile) -> Result<()> {
+    let path = profile_path(name)?;
+    write_atomic(&path, &profile.to_json()?)
+}
+
+/// Adds an offset to a profile, creating the profile if needed. A profile that
+/// cannot be read is left as it is, since its other offsets would be lost.
+pub fn add_to_profile(name: &str, saved: SavedCalibration) -> Result<()> {
+    let mut profile = match read_profile(name) {
+        Ok(profile) => profile,
+        Err(Error::ProfileNotFound(_)) => Profile::default(),
+        Err(e) => return Err(e),
+    };
+    profile.upsert(saved);
+    write_profile(name, &profile)
+}
+
+/// Writes through a temp file in the same dir, so readers never see a partial file.
+pub(crate) fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let Some(dir) = path.parent() else {
+        return Err(Error::InvalidOperation);
+    };
+    fs::create_dir_all(dir)?;
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let tmp = dir.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let mut f = File::create(&tmp)?;
+    let written = f.write_all(contents.as_bytes()).and_then(|_| f.sync_all());
+    drop(f);
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// All profiles in the config dir, sorted by name.
+/// Files that fail to parse are listed with their error.
+pub fn list_profiles() -> Result<Vec<(String, Result<Profile>)>> {
+    let dir = config_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if RESERVED_NAMES.contains(&name) || name.starts_with('.') {
+            continue;
+        }
+        names.push(name.to_string());
+    }
+    names.sort();
+
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            let profile = read_profile(&name);
+            (name, profile)
+        })
+        .collect())
+}
+
+pub fn delete_profile(name: &str) -> Result<()> {
+    let path = profile_path(name)?;
+    if !path.exists() {
+        return Err(Error::ProfileNotFound(name.to_string()));
+    }
+    Ok(fs::remove_file(path)?)
+}
+
+pub fn rename_profile(from: &str, to: &str) -> Result<()> {
+    let (from_path, to_path) = copy_paths(from, to)?;
+    Ok(fs::rename(from_path, to_path)?)
+}
+
+pub fn copy_profile(from: &str, to: &str) -> Result<()> {
+    let (from_path, to_path) = copy_paths(from, to)?;
+    fs::copy(from_path, to_path)?;
+    Ok(())
+}
+
+fn copy_paths(from: &str, to: &str) -> Result<(PathBuf, PathBuf)> {
+    let from_path = profile_path(from)?;
+    let to_path = profile_path(to)?;
+    if !from_path.exists() {
+        return Err(Error::ProfileNotFound(from.to_string()));
+    }
+    if to_path.exists() {
+        return Err(Error::ProfileExists(to.to_string()));
+    }
+    Ok((from_path, to_path))
+}
@@ -0,0 +1,180 @@
+use std::{
+    env,
+    fs::{self, File},
+    io,
+    os::{
+        fd::AsFd,
+        unix::{
+            fs::MetadataExt,
+            net::{SocketAddr, UnixDatagram},
+        },
+    },
+    path::{Path, PathBuf},
+};
+
+use crate::error::Error;
+use crate::profiles::write_atomic;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub const SERVICE_NAME: &str = "motoc.service";
+
+/// What the systemd user service runs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServiceMode {
+    /// `motoc continue --profile NAME`
+    Continue { profile: String },
+    /// `motoc watch`, for one profile or the best fitting one
+    Watch { profile: Option<String> },
+    /// `motoc daemon`
+    Daemon,
+}
+
+impl ServiceMode {
+    /// The arguments after `motoc --wait`.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        match self {
+            ServiceMode::Continue { profile } => {
+                args.extend(["continue", "--profile", profile].map(String::from));
+            }
+            ServiceMode::Watch { profile } => {
+                args.push(String::from("watch"));
+                if let Some(profile) = profile {
+                    args.extend(["--profile", profile].map(String::from));
+                }
+            }
+            ServiceMode::Daemon => args.push(String::from("daemon")),
+        }
+        args
+    }
+}
+
+/// `$XDG_CONFIG_HOME/systemd/user/motoc.service`
+pub fn service_path() -> Result<PathBuf> {
+    let xdg_dirs = xdg::BaseDirectories::new();
+    let mut path = xdg_dirs.get_config_home().ok_or(Error::NoHomeDir)?;
+    path.push("systemd");
+    path.push("user");
+    path.push(SERVICE_NAME);
+    Ok(path)
+}
+
+/// Quotes an argument for `ExecStart=` if it needs it.
+fn exec_arg(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "\"'\\;$".contains(c)) {
+        return arg;
+    }
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "$$");
+    format!("\"{}\"", escaped)
+}
+
+/// The unit file for running `exe` as a systemd user service.
+pub fn render_service(exe: &Path, mode: &ServiceMode) -> String {
+    let mut exec = vec![exe.to_string_lossy().into_owned(), String::from("--wait")];
+    exec.extend(mode.args());
+    let exec: Vec<String> = exec.iter().map(|arg| exec_arg(arg)).collect();
+
+    format!(
+        "# Made by `motoc service install`. Remove with `motoc service uninstall`.
+[Unit]
+Description=Monado Tracking Origin Calibrator
+After=monado.service wivrn.service
+
+[Service]
+Type=notify
+NotifyAccess=main
+ExecStart={}
+Restart=on-failure
+RestartSec=5
+# waiting for Monado and the devices can take any amount of time
+TimeoutStartSec=infinity
+
+[Install]
+WantedBy=default.target
+",
+        exec.join(" ")
+    )
+}
+
+/// Writes the unit file, refusing to replace an existing one unless `overwrite` is set.
+pub fn write_service(exe: &Path, mode: &ServiceMode, overwrite: bool) -> Result<PathBuf> {
+    let path = service_path()?;
+    if !overwrite && path.exists() {
+        return Err(Error::ServiceExists(path.display().to_string()));
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomic(&path, &render_service(exe, mode))?;
+    Ok(path)
+}
+
+/// Removes the unit file. Returns false if there was none.
+pub fn remove_service() -> Result<bool> {
+    let path = service_path()?;
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(path)?;
+    Ok(true)
+}
+
+/// Tells systemd about the service's state, e.g. `READY=1`.
+/// Returns false when not started by systemd with `Type=notify`.
+pub fn sd_notify(state: &str) -> Result<bool> {
+    let Some(socket) = env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let socket = socket.to_string_lossy();
+
+    let addr = match socket.strip_prefix('@') {
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            SocketAddr::from_abstract_name(name)?
+        }
+        None => SocketAddr::from_pathname(socket.as_ref())?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(true)
+}
+
+/// Whether stderr goes to the systemd journal. `JOURNAL_STREAM` is inherited by
+/// child processes, so it only counts if it names the stream stderr is connected
+/// to, see sd_journal_stream_fd(3).
+pub fn logging_to_journal() -> bool {
+    let Some(stream) = env::var_os("JOURNAL_STREAM") else {
+        return false;
+    };
+    // fstat(2) on a duplicate, so that stderr itself stays open
+    let stderr = io::stderr()
+        .as_fd()
+        .try_clone_to_owned()
+        .and_then(|fd| File::from(fd).metadata());
+    match stderr {
+        Ok(meta) => is_journal_stream(&stream.to_string_lossy(), meta.dev(), meta.ino()),
+        Err(_) => false,
+    }
+}
+
+/// Whether a `JOURNAL_STREAM` value, `DEVICE:INODE`, names the given file.
+pub fn is_journal_stream(value: &str, dev: u64, ino: u64) -> bool {
+    let Some((d, i)) = value.split_once(':') else {
+        return false;
+    };
+    d.parse() == Ok(dev) && i.parse() == Ok(ino)
+}
+
+/// The syslog priority the journal reads from a `<N>` prefix on a line.
+pub fn journal_priority(level: log::Level) -> u8 {
+    match level {
+        log::Level::Error => 3,
+        log::Level::Warn => 4,
+        log::Level::Info => 6,
+        log::Level::Debug | log::Level::Trace => 7,
+    }
+}
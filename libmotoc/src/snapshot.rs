@@ -0,0 +1,211 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use libmonado as mnd;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, ResultExt};
+use crate::journal::{self, OffsetTarget};
+use crate::lock::check_offset_unlocked;
+use crate::profiles::{config_dir, is_valid_name, write_atomic};
+use crate::transformd::TransformD;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The STAGE, LOCAL and tracking origin offsets of a running Monado, all at once.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// unix seconds
+    #[serde(default)]
+    pub created: u64,
+    pub stage: TransformD,
+    pub local: TransformD,
+    pub origins: Vec<OriginOffset>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OriginOffset {
+    pub name: String,
+    pub offset: TransformD,
+}
+
+/// One offset compared between a snapshot and the live state.
+/// `saved` or `live` is `None` when the offset exists on only one side.
+pub struct OffsetDiff {
+    pub name: String,
+    pub saved: Option<TransformD>,
+    pub live: Option<TransformD>,
+}
+
+impl OffsetDiff {
+    /// position change in meters
+    pub fn distance(&self) -> Option<f64> {
+        Some((self.live?.origin - self.saved?.origin).norm())
+    }
+
+    /// rotation change in degrees
+    pub fn angle(&self) -> Option<f64> {
+        Some(self.live?.basis.angle_to(&self.saved?.basis).to_degrees())
+    }
+}
+
+impl Snapshot {
+    pub fn capture(monado: &mnd::Monado) -> Result<Self> {
+        let stage = monado
+            .get_reference_space_offset(mnd::ReferenceSpaceType::Stage)
+            .context("Unable to get STAGE offset")?
+            .into();
+        let local = monado
+            .get_reference_space_offset(mnd::ReferenceSpaceType::Local)
+            .context("Unable to get LOCAL offset")?
+            .into();
+
+        let mut origins = Vec::new();
+        for origin in monado
+            .tracking_origins()
+            .context("Unable to enumerate tracking origins")?
+        {
+            origins.push(OriginOffset {
+                offset: origin
+                    .get_offset()
+                    .context(format!("Unable to get offset of {}", origin.name))?
+                    .into(),
+                name: origin.name,
+            });
+        }
+
+        Ok(Self {
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            stage,
+            local,
+            origins,
+        })
+    }
+
+    /// Writes all offsets back, matching tracking origins by name.
+    /// Returns the names of saved origins that are not present anymore.
+    /// Writes nothing if any of the offsets is in use by another motoc process.
+    pub fn restore(&self, monado: &mnd::Monado) -> Result<Vec<String>> {
+        let live: Vec<_> = monado
+            .tracking_origins()
+            .context("Unable to enumerate tracking origins")?
+            .into_iter()
+            .collect();
+
+        let mut missing = Vec::new();
+        let mut writes = Vec::new();
+        for saved in self.origins.iter() {
+            match live.iter().find(|o| o.name == saved.name) {
+                Some(origin) => writes.push((origin, saved)),
+                None => missing.push(saved.name.clone()),
+            }
+        }
+
+        // a lock found halfway through would leave the snapshot half restored
+        for space in [
+            mnd::ReferenceSpaceType::Stage,
+            mnd::ReferenceSpaceType::Local,
+        ] {
+            check_offset_unlocked(&OffsetTarget::from_space(space)?)?;
+        }
+        for (origin, _) in writes.iter() {
+            check_offset_unlocked(&OffsetTarget::TrackingOrigin(origin.name.clone()))?;
+        }
+
+        journal::set_space_offset(
+            monado,
+            mnd::ReferenceSpaceType::Stage,
+            self.stage,
+            "snapshot restore",
+        )
+        .context("Unable to set STAGE offset")?;
+        journal::set_space_offset(
+            monado,
+            mnd::ReferenceSpaceType::Local,
+            self.local,
+            "snapshot restore",
+        )
+        .context("Unable to set LOCAL offset")?;
+
+        for (origin, saved) in writes {
+            journal::set_origin_offset(origin, saved.offset, "snapshot restore")
+                .context(format!("Unable to set offset of {}", saved.name))?;
+        }
+        Ok(missing)
+    }
+
+    /// Compares against another snapshot, usu. a fresh capture of the live state.
+    /// STAGE and LOCAL come first, then origins in the order they were saved.
+    pub fn diff(&self, live: &Snapshot) -> Vec<OffsetDiff> {
+        let mut diffs = vec![
+            OffsetDiff {
+                name: String::from("STAGE"),
+                saved: Some(self.stage),
+                live: Some(live.stage),
+            },
+            OffsetDiff {
+                name: String::from("LOCAL"),
+                saved: Some(self.local),
+                live: Some(live.local),
+            },
+        ];
+
+        for saved in self.origins.iter() {
+            diffs.push(OffsetDiff {
+                name: saved.name.clone(),
+                saved: Some(saved.offset),
+                live: live
+                    .origins
+                    .iter()
+                    .find(|o| o.name == saved.name)
+                    .map(|o| o.offset),
+            });
+        }
+
+        for origin in live.origins.iter() {
+            if self.origins.iter().any(|o| o.name == origin.name) {
+                continue;
+            }
+            diffs.push(OffsetDiff {
+                name: origin.name.clone(),
+                saved: None,
+                live: Some(origin.offset),
+            });
+        }
+
+        diffs
+    }
+}
+
+pub fn snapshot_path(name: &str) -> Result<PathBuf> {
+    if !is_valid_name(name) {
+        return Err(Error::InvalidSnapshotName(name.to_string()));
+    }
+
+    let mut path = config_dir()?;
+    path.push("snapshots");
+    path.push(format!("{}.json", name));
+    Ok(path)
+}
+
+pub fn read_snapshot(name: &str) -> Result<Snapshot> {
+    let path = snapshot_path(name)?;
+    if !path.exists() {
+        return Err(Error::SnapshotNotFound(name.to_string()));
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Saves the snapshot, refusing to replace an existing one unless `overwrite` is set.
+pub fn write_snapshot(name: &str, snapshot: &Snapshot, overwrite: bool) -> Result<()> {
+    let path = snapshot_path(name)?;
+    if !overwrite && path.exists() {
+        return Err(Error::SnapshotExists(name.to_string()));
+    }
+    write_atomic(&path, &serde_json::to_string_pretty(snapshot)?)
+}
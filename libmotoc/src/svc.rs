@@ -0,0 +1,109 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nalgebra::{Rotation3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::common::{CalibrationMeta, OffsetType, SavedCalibration};
+use crate::error::Error;
+use crate::transformd::TransformD;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn one() -> f64 {
+    1.0
+}
+
+/// A calibration as saved by OpenVR-SpaceCalibrator.
+///
+/// It maps poses of the target tracking system into the reference one, which is
+/// what a motoc tracking origin offset does. Translation is in centimeters.
+/// Rotation is in degrees, applied as `Rz(roll) * Ry(yaw) * Rx(pitch)`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SvcProfile {
+    pub reference_tracking_system: String,
+    pub target_tracking_system: String,
+    pub roll: f64,
+    pub yaw: f64,
+    pub pitch: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    #[serde(default = "one")]
+    pub scale: f64,
+}
+
+impl SvcProfile {
+    /// Parses a profile file, which holds an array with the active profile first.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let first = match value {
+            serde_json::Value::Array(mut profiles) if !profiles.is_empty() => {
+                profiles.swap_remove(0)
+            }
+            serde_json::Value::Object(_) => value,
+            _ => {
+                return Err(Error::InvalidSvcProfile(String::from(
+                    "expected a list of profiles",
+                )))
+            }
+        };
+        Ok(serde_json::from_value(first)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&[self])?)
+    }
+
+    /// The offset of the target tracking system, in meters.
+    pub fn offset(&self) -> TransformD {
+        TransformD {
+            origin: Vector3::new(self.x, self.y, self.z) / 100.0,
+            basis: Rotation3::from_euler_angles(
+                self.pitch.to_radians(),
+                self.yaw.to_radians(),
+                self.roll.to_radians(),
+            ),
+        }
+    }
+
+    pub fn from_offset(reference: &str, target: &str, offset: &TransformD) -> Self {
+        let (pitch, yaw, roll) = offset.basis.euler_angles();
+        let origin = offset.origin * 100.0;
+        Self {
+            reference_tracking_system: reference.to_string(),
+            target_tracking_system: target.to_string(),
+            roll: roll.to_degrees(),
+            yaw: yaw.to_degrees(),
+            pitch: pitch.to_degrees(),
+            x: origin.x,
+            y: origin.y,
+            z: origin.z,
+            scale: 1.0,
+        }
+    }
+
+    /// Converts to a tracking origin offset. The origin names default to the
+    /// tracking system names, which rarely match Monado's.
+    pub fn into_saved(self, src: Option<String>, dst: Option<String>) -> SavedCalibration {
+        if (self.scale - 1.0).abs() > 1e-6 {
+            log::warn!(
+                "Ignoring scale {:.4}, Monado offsets cannot scale.",
+                self.scale
+            );
+        }
+
+        SavedCalibration {
+            offset_type: OffsetType::TrackingOrigin,
+            offset: self.offset(),
+            src: src.unwrap_or(self.reference_tracking_system),
+            dst: dst.unwrap_or(self.target_tracking_system),
+            meta: CalibrationMeta {
+                created: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs()),
+                motoc_version: env!("CARGO_PKG_VERSION").to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+
+use crate::common::CalibratorData;
+use crate::helpers_xr::SpaceLocationConvert;
+use crate::transformd::TransformD;
+
+/// Figures gathered while calibrating, for reporting them elsewhere.
+#[derive(Clone, Debug, Default)]
+pub struct Telemetry {
+    /// whether continuous mode runs
+    pub continuous: bool,
+    /// how far continuous mode is from the target offset, while it runs
+    pub deviation: Option<Deviation>,
+    /// number of offset updates continuous mode made
+    pub corrections: u64,
+    /// sum of the offset updates, in meters
+    pub correction_meters: f64,
+    /// sum of the offset updates, in degrees
+    pub correction_degrees: f64,
+    /// times continuous mode saw an offset too far from the center
+    pub anomalies: u64,
+    /// whether the offset is too far from the center right now
+    pub anomaly: bool,
+    /// times an anomaly lasted long enough to reset the offset
+    pub anomaly_resets: u64,
+    /// times each device, by serial, stopped tracking
+    pub tracking_losses: BTreeMap<String, u64>,
+    /// whether each device, by serial, tracked at the last observation
+    pub tracked: BTreeMap<String, bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Deviation {
+    pub meters: f64,
+    pub degrees: f64,
+}
+
+impl Deviation {
+    pub fn of(delta: &TransformD) -> Self {
+        Self {
+            meters: delta.origin.norm(),
+            degrees: delta.basis.angle().to_degrees(),
+        }
+    }
+}
+
+impl Telemetry {
+    /// Counts an offset update by continuous mode.
+    pub fn correction(&mut self, before: &TransformD, after: &TransformD) {
+        self.corrections += 1;
+        self.correction_meters += (after.origin - before.origin).norm();
+        self.correction_degrees += (after.basis * before.basis.inverse()).angle().to_degrees();
+    }
+
+    /// Records whether a device tracks. Returns true if it just stopped.
+    pub fn tracking(&mut self, serial: &str, tracked: bool) -> bool {
+        let was = self.tracked.insert(serial.to_string(), tracked);
+        let lost = was == Some(true) && !tracked;
+        if lost {
+            *self.tracking_losses.entry(serial.to_string()).or_default() += 1;
+        }
+        lost
+    }
+}
+
+impl CalibratorData<'_> {
+    /// Updates which devices are tracking, counting tracking losses.
+    pub fn observe_tracking(&mut self) {
+        for device in self.devices.iter() {
+            let tracked = device.connected
+                && device
+                    .space
+                    .locate(&self.stage, self.now)
+                    .is_ok_and(|loc| loc.into_transformd().is_ok());
+            self.telemetry.tracking(&device.serial, tracked);
+        }
+    }
+}
@@ -1,7 +1,27 @@
-use nalgebra::Rotation3;
+use nalgebra::{Quaternion, Rotation3, Unit, UnitQuaternion};
+use openxr as xr;
 
 use crate::{
-    common::{vec3, UNIT},
+    aliases::{similarity, Aliases, NameKind, Resolution},
+    calibrator::{solve_point_pairs, turn_axis, CalibratorStatus, RigidPair},
+    clients::Clients,
+    common::{
+        merge_devices, vec3, CalibrationMeta, DeviceMerge, OffsetType, SavedCalibration, UNIT,
+    },
+    config::{Config, Tuning},
+    daemon::{parse_request, DaemonReply, DaemonRequest, DaemonStatus},
+    error::Error,
+    helpers_xr::HandJointSource,
+    journal::{next_id, undoable, JournalEntry, OffsetTarget},
+    metrics::{MetricsServer, MetricsText},
+    monado_config::{merge_tracking_overrides, tracking_override},
+    osc::{encode_osc, OscArg, OscOutput, OscSender},
+    presets::builtin_presets,
+    profiles::Profile,
+    service::{is_journal_stream, journal_priority, render_service, ServiceMode},
+    snapshot::{OriginOffset, Snapshot},
+    svc::SvcProfile,
+    telemetry::{Deviation, Telemetry},
     transformd::TransformD,
 };
 
@@ -93,3 +113,985 @@ pub fn transform_hierarchy() {
         "offset"
     );
 }
+
+#[test]
+pub fn point_pairs() {
+    let dst_to_src = TransformD {
+        origin: vec3(1.2, -0.3, 0.75),
+        basis: euler_zxy(135f64.to_radians(), 3f64.to_radians(), -2f64.to_radians()),
+    };
+
+    let dst = [
+        vec3(0.0, 0.0, 0.0),
+        vec3(1.0, 0.0, 0.2),
+        vec3(0.3, 0.9, -0.5),
+        vec3(-0.7, 0.1, 1.1),
+    ];
+    let src = dst.map(|p| dst_to_src.origin + dst_to_src.basis * p);
+
+    let solved = solve_point_pairs(&src, &dst).expect("points are not collinear");
+    assert_eq!(mismatch(solved, dst_to_src), "", "solved");
+
+    let collinear = [
+        vec3(0.0, 0.0, 0.0),
+        vec3(1.0, 1.0, 0.0),
+        vec3(2.0, 2.0, 0.0),
+    ];
+    assert!(
+        solve_point_pairs(&collinear, &collinear).is_none(),
+        "collinear"
+    );
+}
+
+#[test]
+pub fn hand_joint_source() {
+    let wrist = HandJointSource::parse("hand:left:wrist").unwrap().unwrap();
+    assert_eq!(wrist.hand, xr::HandEXT::LEFT);
+    assert_eq!(wrist.joint, xr::HandJointEXT::WRIST);
+
+    let tip = HandJointSource::parse("Hand:Right:Index-Tip")
+        .unwrap()
+        .unwrap();
+    assert_eq!(tip.joint, xr::HandJointEXT::INDEX_TIP);
+    assert_eq!(tip.to_string(), "hand:right:index-tip");
+
+    assert!(HandJointSource::parse("LHR-ABCDE000").unwrap().is_none());
+    assert!(HandJointSource::parse("hand:left:elbow").is_err());
+}
+
+#[test]
+pub fn rigid_pair() {
+    let mount = TransformD {
+        origin: vec3(0.05, -0.1, 0.12),
+        basis: euler_zxy(170f64.to_radians(), 20f64.to_radians(), 5f64.to_radians()),
+    };
+    let origin_offset = TransformD {
+        origin: vec3(2.0, 0.3, -1.5),
+        basis: euler_zxy(75f64.to_radians(), 0., 0.),
+    };
+
+    let mut attached = RigidPair::default();
+    let mut loose = RigidPair::default();
+
+    for i in 0..40 {
+        let t = i as f64 * 0.1;
+        let head = TransformD {
+            origin: vec3(t.sin() * 0.3, 1.6, t.cos() * 0.2),
+            basis: euler_zxy(t.sin(), (t * 1.3).cos() * 0.4, t.sin() * 0.2),
+        };
+        let other = TransformD {
+            origin: vec3(0.5, 1.0, 0.0),
+            basis: euler_zxy(t * 0.05, 0., 0.),
+        };
+
+        attached.push(Some((head, origin_offset * head * mount)));
+        loose.push(Some((head, other)));
+    }
+
+    let attached = attached.mean_mismatch().expect("devices moved");
+    let loose = loose.mean_mismatch().expect("devices moved");
+    assert!(attached.degrees < 0.01, "attached: {attached:?}");
+    assert!(attached.meters < 0.001, "attached: {attached:?}");
+    assert!(attached.is_rigid());
+    assert!(loose.degrees > 5.0, "loose: {loose:?}");
+}
+
+#[test]
+pub fn rigid_pair_same_angle() {
+    // both devices always turn by the same angle, but A turns about X and then Y,
+    // while B turns about Y all along. no single mount explains that.
+    let mut pair = RigidPair::default();
+    for i in 0..126 {
+        let t = i as f64 * 0.1;
+        let angle = (t.sin() * 0.8).abs();
+        let axis_a = if t.sin() >= 0.0 { UNIT.XU } else { UNIT.YU };
+        let pose = |axis| TransformD {
+            origin: vec3(0.0, 1.6, 0.0),
+            basis: Rotation3::from_axis_angle(&axis, angle),
+        };
+        pair.push(Some((pose(axis_a), pose(UNIT.YU))));
+    }
+
+    let mismatch = pair.mean_mismatch().expect("devices moved");
+    assert!(mismatch.degrees > 5.0, "same angle: {mismatch:?}");
+    assert!(!mismatch.is_rigid());
+}
+
+#[test]
+pub fn level_turn_axis() {
+    let floor_up = Unit::new_normalize(vec3(0.03, 1.0, -0.05));
+    let lying = UnitQuaternion::from_rotation_matrix(&euler_zxy(0.3, 1.5, 0.1));
+
+    let placements = [0f64, 85., 170., 260.]
+        .map(|deg| UnitQuaternion::from_axis_angle(&floor_up, deg.to_radians()) * lying);
+
+    let up = turn_axis(&placements).expect("device was turned");
+    assert!((up - floor_up.into_inner()).norm() < 1e-6, "axis");
+
+    assert!(turn_axis(&[lying, lying]).is_none(), "not turned");
+}
+
+#[test]
+pub fn legacy_profile() {
+    let legacy = r#"{"offset_type":"Device","src":"HMD","dst":"LHR-ABCDE000",
+        "offset":{"basis":[1,0,0,0,1,0,0,0,1],"origin":[0.1,0.2,0.3]}}"#;
+
+    let profile = Profile::from_json(legacy).unwrap();
+    assert_eq!(profile.offsets.len(), 1);
+    let saved = &profile.offsets[0];
+    assert_eq!(saved.dst, "LHR-ABCDE000");
+    assert_eq!(saved.meta.created, 0);
+    assert!(saved.meta.samples.is_none());
+    assert_eq!(saved.offset.origin, vec3(0.1, 0.2, 0.3));
+}
+
+#[test]
+pub fn profile_round_trip() {
+    let saved = |dst: &str, yaw: f64| SavedCalibration {
+        offset_type: OffsetType::TrackingOrigin,
+        src: String::from("Monado"),
+        dst: dst.to_string(),
+        offset: TransformD {
+            origin: vec3(1.0, 0.5, -2.0),
+            basis: euler_zxy(yaw, 0.1, -0.2),
+        },
+        meta: CalibrationMeta::default(),
+    };
+
+    let mut profile = Profile::default();
+    profile.upsert(saved("Lighthouse", 0.3));
+    profile.upsert(saved("WMR", 1.2));
+    profile.upsert(saved("Lighthouse", -0.7));
+    assert_eq!(profile.offsets.len(), 2);
+
+    let json = profile.to_json().unwrap();
+    let parsed = Profile::from_json(&json).unwrap();
+    assert_eq!(parsed.offsets.len(), 2);
+    for (a, b) in profile.offsets.iter().zip(parsed.offsets.iter()) {
+        assert_eq!(a.dst, b.dst);
+        let m = mismatch(a.offset, b.offset);
+        if !m.is_empty() {
+            panic!("round trip of {} changed the {}", a.dst, m);
+        }
+    }
+
+    // euler angles edited by hand must not be silently dropped, or silently win
+    let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    value["offsets"][0]["euler"] = serde_json::json!({"yaw": 90.0, "pitch": 0.0, "roll": 0.0});
+    let error = Profile::from_json(&value.to_string()).err().unwrap();
+    assert!(
+        error.to_string().contains("yaw 90 pitch 0 roll 0"),
+        "{error}"
+    );
+
+    // they apply once the quaternion is gone
+    value["offsets"][0]["rotation"] = serde_json::Value::Null;
+    let edited = Profile::from_json(&value.to_string()).unwrap();
+    let expected = Rotation3::from_euler_angles(0.0, 0.0, 90f64.to_radians());
+    assert!(edited.offsets[0].offset.basis.angle_to(&expected) < 1e-6);
+
+    value["version"] = serde_json::json!(99);
+    assert!(Profile::from_json(&value.to_string()).is_err());
+}
+
+#[test]
+pub fn snapshot_diff() {
+    let origin = |name: &str, x: f64, yaw_deg: f64| OriginOffset {
+        name: name.to_string(),
+        offset: TransformD {
+            origin: vec3(x, 0., 0.),
+            basis: euler_zxy(yaw_deg.to_radians(), 0., 0.),
+        },
+    };
+    let snapshot = |origins| Snapshot {
+        created: 0,
+        stage: TransformD::default(),
+        local: TransformD::default(),
+        origins,
+    };
+
+    let saved = snapshot(vec![
+        origin("Lighthouse", 1.0, 10.0),
+        origin("WMR", 0.0, 0.0),
+    ]);
+    let live = snapshot(vec![
+        origin("Lighthouse", 1.5, 40.0),
+        origin("Hand", 0.0, 0.0),
+    ]);
+
+    let diffs = saved.diff(&live);
+    let names: Vec<&str> = diffs.iter().map(|d| d.name.as_str()).collect();
+    assert_eq!(names, ["STAGE", "LOCAL", "Lighthouse", "WMR", "Hand"]);
+
+    assert_eq!(diffs[0].distance(), Some(0.0));
+    assert!((diffs[2].distance().unwrap() - 0.5).abs() < 1e-9);
+    assert!((diffs[2].angle().unwrap() - 30.0).abs() < 1e-6);
+    assert!(diffs[3].live.is_none() && diffs[3].angle().is_none());
+    assert!(diffs[4].saved.is_none() && diffs[4].distance().is_none());
+}
+
+#[test]
+pub fn journal_undoable() {
+    let entry = |id: u64, reverts: Option<u64>| JournalEntry {
+        id,
+        time: 0,
+        source: String::from("adjust"),
+        target: OffsetTarget::Stage,
+        previous: TransformD::default(),
+        new: TransformD::default(),
+        reverts,
+        ongoing: false,
+        completes: None,
+    };
+
+    // 3 was undone by 4, so the next undo goes to 2 and then 1
+    let entries = vec![
+        entry(1, None),
+        entry(2, None),
+        entry(3, None),
+        entry(4, Some(3)),
+    ];
+    let ids: Vec<u64> = undoable(&entries).iter().map(|e| e.id).collect();
+    assert_eq!(ids, [2, 1]);
+
+    let entries = vec![entry(1, Some(0)), entry(2, Some(1))];
+    assert!(undoable(&entries).is_empty());
+
+    // continuous mode that stopped cleanly is undone as one change,
+    // one that was interrupted can still be undone from where it started
+    let ongoing = |id: u64| JournalEntry {
+        ongoing: true,
+        ..entry(id, None)
+    };
+    let entries = vec![
+        ongoing(1),
+        JournalEntry {
+            completes: Some(1),
+            ..entry(2, None)
+        },
+        ongoing(3),
+    ];
+    let ids: Vec<u64> = undoable(&entries).iter().map(|e| e.id).collect();
+    assert_eq!(ids, [3, 2]);
+
+    let dir = std::env::temp_dir().join(format!("motoc-journal-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("journal.jsonl");
+    assert_eq!(next_id(&path).unwrap(), 1);
+    let lines: Vec<String> = (1..=40)
+        .map(|id| serde_json::to_string(&entry(id, None)).unwrap())
+        .collect();
+    std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+    assert_eq!(next_id(&path).unwrap(), 41);
+    // a broken last line falls back to reading all of it
+    std::fs::write(&path, lines.join("\n") + "\n{\"id\": 4").unwrap();
+    assert_eq!(next_id(&path).unwrap(), 41);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+pub fn svc_profile() {
+    let json = r#"[{"reference_tracking_system":"oculus","target_tracking_system":"lighthouse",
+        "roll":90.0,"yaw":0.0,"pitch":0.0,"x":100.0,"y":-50.0,"z":0.0,"scale":1.0}]"#;
+
+    let svc = SvcProfile::from_json(json).unwrap();
+    let offset = svc.offset();
+    assert!((offset.origin - vec3(1.0, -0.5, 0.0)).norm() < 1e-9);
+    // roll is about Z
+    assert!((offset.basis * UNIT.X - UNIT.Y).norm() < 1e-9);
+
+    let expected = TransformD {
+        origin: vec3(0.3, 1.2, -0.8),
+        basis: euler_zxy(0.4, -0.2, 0.1),
+    };
+    let back = SvcProfile::from_json(
+        &SvcProfile::from_offset("a", "b", &expected)
+            .to_json()
+            .unwrap(),
+    )
+    .unwrap();
+    let m = mismatch(back.offset(), expected);
+    if !m.is_empty() {
+        panic!("svc round trip changed the {}", m);
+    }
+
+    let saved = back.into_saved(Some(String::from("Monado")), None);
+    assert_eq!((saved.src.as_str(), saved.dst.as_str()), ("Monado", "b"));
+}
+
+#[test]
+pub fn monado_tracking_override() {
+    let device_offset = SavedCalibration {
+        offset_type: OffsetType::Device,
+        src: String::from("WiVRn HMD"),
+        dst: String::from("LHR-ABCDE000"),
+        offset: TransformD {
+            origin: vec3(0.05, 0.1, -0.08),
+            basis: euler_zxy(0.3, -0.5, 0.2),
+        },
+        meta: CalibrationMeta::default(),
+    };
+
+    let o = tracking_override(&device_offset).unwrap();
+    assert_eq!(o["target_device_serial"], "WiVRn HMD");
+    assert_eq!(o["tracker_device_serial"], "LHR-ABCDE000");
+
+    // monado puts the target at tracker * offset⁻¹, motoc at tracker * offset
+    let (p, q) = (&o["offset"]["position"], &o["offset"]["orientation"]);
+    let f = |v: &serde_json::Value| v.as_f64().unwrap();
+    let exported = TransformD {
+        origin: vec3(f(&p["x"]), f(&p["y"]), f(&p["z"])),
+        basis: UnitQuaternion::from_quaternion(Quaternion::new(
+            f(&q["w"]),
+            f(&q["x"]),
+            f(&q["y"]),
+            f(&q["z"]),
+        ))
+        .to_rotation_matrix(),
+    };
+    let tracker = TransformD {
+        origin: vec3(1.0, 1.6, -2.0),
+        basis: euler_zxy(1.1, 0.1, 0.0),
+    };
+    let m = mismatch(tracker * exported.inverse(), tracker * device_offset.offset);
+    if !m.is_empty() {
+        panic!("tracking override changed the {}", m);
+    }
+
+    let origin_offset = SavedCalibration {
+        offset_type: OffsetType::TrackingOrigin,
+        ..device_offset
+    };
+    assert!(tracking_override(&origin_offset).is_none());
+
+    let mut config = serde_json::json!({
+        "active": "tracking",
+        "tracking": { "version": 0, "tracking_overrides": [o.clone()] },
+    });
+    merge_tracking_overrides(&mut config, &[o]).unwrap();
+    assert_eq!(config["active"], "tracking");
+    assert_eq!(
+        config["tracking"]["tracking_overrides"]
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+pub fn profile_match() {
+    let saved = |offset_type, src: &str, dst: &str| SavedCalibration {
+        offset_type,
+        src: src.to_string(),
+        dst: dst.to_string(),
+        offset: TransformD::default(),
+        meta: CalibrationMeta::default(),
+    };
+    let serials = ["WiVRn HMD", "LHR-ABCDE000"];
+    let origins = ["WiVRn", "Lighthouse"];
+
+    let device = Profile {
+        offsets: vec![saved(OffsetType::Device, "WiVRn HMD", "LHR-ABCDE000")],
+    };
+    let other_tracker = Profile {
+        offsets: vec![saved(OffsetType::Device, "WiVRn HMD", "LHR-FFFFF000")],
+    };
+    let origin = Profile {
+        offsets: vec![saved(OffsetType::TrackingOrigin, "Quest", "Lighthouse")],
+    };
+
+    let mut aliases = Aliases::default();
+
+    let fit = device.match_against(&serials, &origins, &aliases);
+    assert_eq!(fit.usable, 1);
+    let missing = other_tracker.match_against(&serials, &origins, &aliases);
+    assert_eq!(missing.usable, 0);
+    assert!(missing.reasons[0].contains("LHR-FFFFF000"));
+
+    // the source origin is missing, so it applies against identity and ranks lower
+    let partial = origin.match_against(&serials, &origins, &aliases);
+    assert_eq!(partial.usable, 1);
+    assert!(partial.score < fit.score);
+
+    aliases.add(NameKind::Origin, "Quest", "WiVRn");
+    let aliased = origin.match_against(&serials, &origins, &aliases);
+    assert_eq!(aliased.score, fit.score);
+}
+
+#[test]
+pub fn name_resolution() {
+    let live = ["WiVRn HMD Origin", "SteamVR Lighthouse"];
+    let mut aliases = Aliases::default();
+
+    assert_eq!(
+        aliases.resolve(NameKind::Origin, "SteamVR Lighthouse", &live),
+        Resolution::Exact("SteamVR Lighthouse")
+    );
+    assert_eq!(
+        aliases.resolve(NameKind::Origin, "steamvr-lighthouse", &live),
+        Resolution::Fuzzy("SteamVR Lighthouse")
+    );
+    assert_eq!(
+        aliases.resolve(NameKind::Origin, "Lighthouse", &live),
+        Resolution::Missing
+    );
+
+    aliases.add(NameKind::Origin, "Lighthouse", "SteamVR Lighthouse");
+    assert_eq!(
+        aliases.resolve(NameKind::Origin, "Lighthouse", &live),
+        Resolution::Alias("SteamVR Lighthouse")
+    );
+    // aliases are per kind
+    assert!(!aliases.same(NameKind::Device, "Lighthouse", "SteamVR Lighthouse"));
+    assert!(aliases.remove(NameKind::Origin, "SteamVR Lighthouse"));
+    assert!(aliases.origins.is_empty());
+
+    assert_eq!(similarity("LHR-ABCDE000", "lhr_abcde000"), 1.0);
+    assert!(similarity("LHR-ABCDE000", "LHR-FFFFF000") < 0.75);
+}
+
+#[test]
+pub fn serial_resolution() {
+    let aliases = Aliases::default();
+
+    // a different tracker, however close the serial
+    assert_eq!(
+        aliases.resolve(NameKind::Device, "LHR-ABCDE000", &["LHR-ABCDE001"]),
+        Resolution::Missing
+    );
+    assert_eq!(
+        aliases.resolve(NameKind::Device, "LHR-ABCDE000", &["lhr_abcde000"]),
+        Resolution::Fuzzy("lhr_abcde000")
+    );
+    // the same difference in an origin name is only a typo
+    assert_eq!(
+        aliases.resolve(NameKind::Origin, "LHR-ABCDE000", &["LHR-ABCDE001"]),
+        Resolution::Fuzzy("LHR-ABCDE001")
+    );
+}
+
+#[test]
+pub fn tuning_config() {
+    let config = Config::from_toml(
+        r#"
+        samples = 300
+        lerp_factor = 0.1
+
+        [profile.desk]
+        lerp_factor = 0.5
+        anomaly_distance = 20.0
+        "#,
+    )
+    .unwrap();
+
+    let global = config.tuning(None).unwrap();
+    assert_eq!(global.samples, 300);
+    assert_eq!(global.lerp_factor, 0.1);
+    assert_eq!(global.anomaly_distance, Tuning::default().anomaly_distance);
+
+    let desk = config.tuning(Some("desk")).unwrap();
+    assert_eq!(desk.samples, 300);
+    assert_eq!(desk.lerp_factor, 0.5);
+    assert_eq!(desk.anomaly_distance, 20.0);
+    assert_eq!(config.tuning(Some("other")).unwrap(), global);
+
+    assert!(Config::from_toml("sample = 300").is_err());
+    assert!(Config::from_toml("[profile.desk]\nsamples = \"many\"").is_err());
+
+    for bad in [
+        "samples = 0",
+        "lerp_factor = 0.0",
+        "lerp_factor = 1.5",
+        "offset_lerp_factor = nan",
+        "max_linear_speed = -1.0",
+        "anomaly_reset_secs = -5.0",
+        "anomaly_reset_secs = inf",
+        "anomaly_reset_secs = 1e30",
+        "anomaly_distance = inf",
+        "max_angular_speed = inf",
+    ] {
+        assert!(
+            matches!(Config::from_toml(bad), Err(Error::InvalidConfig(_))),
+            "{}",
+            bad
+        );
+    }
+    let err = Config::from_toml("[profile.desk]\nlerp_factor = 2.0")
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(
+        err.contains("lerp_factor") && err.contains("[profile.desk]"),
+        "{}",
+        err
+    );
+    assert!(Config::from_toml("lerp_factor = 1.0\nmax_angular_speed = 0.0").is_ok());
+}
+
+#[test]
+pub fn device_merge() {
+    let known = [("HMD", true), ("LHR-A", true), ("LHR-B", false)];
+
+    // nothing changed
+    assert_eq!(
+        merge_devices(&known, &["HMD", "LHR-A"]),
+        DeviceMerge {
+            slots: vec![Some(0), Some(1)],
+            removed: vec![],
+        }
+    );
+
+    // LHR-A went away, LHR-B came back in its old slot, LHR-C is new
+    assert_eq!(
+        merge_devices(&known, &["LHR-C", "LHR-B", "HMD"]),
+        DeviceMerge {
+            slots: vec![None, Some(2), Some(0)],
+            removed: vec![1],
+        }
+    );
+}
+
+#[test]
+pub fn service_unit() {
+    let unit = render_service(
+        std::path::Path::new("/usr/bin/motoc"),
+        &ServiceMode::Continue {
+            profile: String::from("last"),
+        },
+    );
+    assert!(unit.contains("\nExecStart=/usr/bin/motoc --wait continue --profile last\n"));
+    assert!(unit.contains("\nType=notify\n"));
+    assert!(unit.contains("\nWantedBy=default.target\n"));
+
+    // paths and names with spaces or specifiers stay one argument each
+    let unit = render_service(
+        std::path::Path::new("/home/me/my apps/motoc"),
+        &ServiceMode::Watch {
+            profile: Some(String::from("100% desk")),
+        },
+    );
+    assert!(unit.contains(
+        "\nExecStart=\"/home/me/my apps/motoc\" --wait watch --profile \"100%% desk\"\n"
+    ));
+
+    assert_eq!(ServiceMode::Daemon.args(), ["daemon"]);
+    assert_eq!(journal_priority(log::Level::Error), 3);
+    assert_eq!(journal_priority(log::Level::Info), 6);
+
+    assert!(is_journal_stream("8:1234", 8, 1234));
+    // e.g. inherited from a service that started motoc with stderr elsewhere
+    assert!(!is_journal_stream("8:1234", 8, 999));
+    assert!(!is_journal_stream("9:1234", 8, 1234));
+    assert!(!is_journal_stream("8", 8, 1234));
+    assert!(!is_journal_stream("", 8, 1234));
+}
+
+#[test]
+pub fn daemon_requests() {
+    assert_eq!(
+        parse_request(serde_json::json!("status"), None).unwrap(),
+        DaemonRequest::Status
+    );
+    assert_eq!(
+        parse_request(serde_json::json!("continue"), None).unwrap(),
+        DaemonRequest::Continue {
+            profile: None,
+            fuzzy: false
+        }
+    );
+    assert_eq!(
+        parse_request(
+            serde_json::json!("calibrate"),
+            Some(serde_json::json!({ "src": "WiVRn HMD", "dst": "LHR-ABCDE000" }))
+        )
+        .unwrap(),
+        DaemonRequest::Calibrate {
+            src: String::from("WiVRn HMD"),
+            dst: String::from("LHR-ABCDE000"),
+            continuous: false,
+            samples: None,
+            no_wait: false,
+            profile: String::from("last"),
+        }
+    );
+    assert_eq!(
+        parse_request(serde_json::json!("floor"), None).unwrap(),
+        DaemonRequest::Floor
+    );
+    assert_eq!(
+        parse_request(
+            serde_json::json!("reset"),
+            Some(serde_json::json!({ "id": "0" }))
+        )
+        .unwrap(),
+        DaemonRequest::Reset {
+            id: String::from("0")
+        }
+    );
+    assert!(parse_request(serde_json::json!("calibrate"), None).is_err());
+    assert!(parse_request(serde_json::json!("explode"), None).is_err());
+
+    // requests go out in the same shape they are parsed from
+    let adjust = DaemonRequest::Adjust {
+        id: String::from("STAGE"),
+        relative: true,
+        yaw: None,
+        x: Some(0.1),
+        y: None,
+        z: None,
+    };
+    let mut wire = serde_json::to_value(&adjust).unwrap();
+    assert_eq!(
+        parse_request(wire["method"].take(), Some(wire["params"].take())).unwrap(),
+        adjust
+    );
+
+    let status = DaemonReply::Status(DaemonStatus {
+        calibrator: Some(String::from("continue")),
+        ..Default::default()
+    });
+    let text = serde_json::to_string(&status).unwrap();
+    assert_eq!(serde_json::from_str::<DaemonReply>(&text).unwrap(), status);
+    assert_eq!(
+        serde_json::from_str::<DaemonReply>("\"Started.\"").unwrap(),
+        DaemonReply::Message(String::from("Started."))
+    );
+}
+
+#[test]
+pub fn metrics_text() {
+    let mut m = MetricsText::default();
+    m.family(
+        "motoc_tracking_losses",
+        "counter",
+        "Times a device stopped tracking.",
+    );
+    m.sample(
+        "motoc_tracking_losses_total",
+        &[("serial", "LHR-\"A\"\\1")],
+        2.0,
+    );
+    m.family(
+        "motoc_device_charging",
+        "gauge",
+        "Whether the device is charging.",
+    );
+    m.sample("motoc_device_charging", &[], 0.5);
+    assert_eq!(
+        m.finish(),
+        "# TYPE motoc_tracking_losses counter
+# HELP motoc_tracking_losses Times a device stopped tracking.
+motoc_tracking_losses_total{serial=\"LHR-\\\"A\\\"\\\\1\"} 2
+# TYPE motoc_device_charging gauge
+# HELP motoc_device_charging Whether the device is charging.
+motoc_device_charging 0.5
+# EOF
+"
+    );
+}
+
+#[test]
+pub fn telemetry_counters() {
+    // only going from tracked to lost counts
+    let mut t = Telemetry::default();
+    assert!(!t.tracking("LHR-A", false));
+    assert!(!t.tracking("LHR-A", true));
+    assert!(t.tracking("LHR-A", false));
+    assert!(!t.tracking("LHR-A", false));
+    assert_eq!(t.tracking_losses["LHR-A"], 1);
+
+    let before = TransformD::default();
+    let after = TransformD {
+        origin: vec3(0.3, 0.0, 0.4),
+        basis: euler_zxy(std::f64::consts::FRAC_PI_2, 0.0, 0.0),
+    };
+    t.correction(&before, &after);
+    t.correction(&after, &after);
+    assert_eq!(t.corrections, 2);
+    assert!((t.correction_meters - 0.5).abs() < 1e-9);
+    assert!((t.correction_degrees - 90.0).abs() < 1e-6);
+    let deviation = Deviation::of(&after);
+    assert!((deviation.meters - 0.5).abs() < 1e-9);
+    assert!((deviation.degrees - 90.0).abs() < 1e-6);
+}
+
+#[test]
+pub fn metrics_server() {
+    let mut server = MetricsServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = server.local_addr().unwrap();
+    let get = |path: &str| {
+        let path = path.to_string();
+        std::thread::spawn(move || {
+            use std::io::{Read, Write};
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut answer = String::new();
+            stream.read_to_string(&mut answer).unwrap();
+            answer
+        })
+    };
+    for (path, status, body) in [
+        ("/metrics", "HTTP/1.1 200 OK", "motoc_up 1\n# EOF\n"),
+        ("/nope", "HTTP/1.1 404 Not Found", ""),
+    ] {
+        let client = get(path);
+        while !client.is_finished() {
+            server.poll(|| String::from("motoc_up 1\n# EOF\n"));
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let answer = client.join().unwrap();
+        assert!(answer.starts_with(status), "{}", answer);
+        assert!(answer.contains("Content-Type: application/openmetrics-text"));
+        assert!(answer.ends_with(&format!("\r\n\r\n{}", body)), "{}", answer);
+    }
+}
+
+#[test]
+pub fn idle_clients() {
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut clients = Clients::new(listener, Duration::from_millis(50), "Test");
+
+    let mut idle = std::net::TcpStream::connect(addr).unwrap();
+    let mut talking = std::net::TcpStream::connect(addr).unwrap();
+    let mut seen = 0;
+    for _ in 0..20 {
+        talking.write_all(b"x").unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        clients.poll(|conn| {
+            seen += conn.buf.len();
+            conn.buf.clear();
+            true
+        });
+    }
+    assert!(seen > 0);
+
+    // the server hung up on the silent client only
+    idle.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    assert_eq!(idle.read(&mut [0u8; 1]).unwrap(), 0);
+    talking
+        .set_read_timeout(Some(Duration::from_millis(50)))
+        .unwrap();
+    assert!(talking.read(&mut [0u8; 1]).is_err());
+}
+
+#[test]
+pub fn osc_output() {
+    assert_eq!(
+        encode_osc(
+            "/motoc/anomaly",
+            &[
+                OscArg::Int(1),
+                OscArg::Float(0.5),
+                OscArg::Str(String::from("hi"))
+            ]
+        ),
+        b"/motoc/anomaly\0\0,ifs\0\0\0\0\0\0\0\x01\x3f\0\0\0hi\0\0"
+    );
+
+    let listener = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    listener
+        .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+        .unwrap();
+    let sender = OscSender::connect(&listener.local_addr().unwrap().to_string()).unwrap();
+    assert_eq!(sender.target(), listener.local_addr().unwrap());
+    let mut osc = OscOutput::new(sender);
+
+    let mut received = Vec::new();
+    let mut receive = |count: usize| {
+        let mut buf = [0u8; 256];
+        for _ in 0..count {
+            let n = listener.recv(&mut buf).unwrap();
+            received.push(buf[..n].to_vec());
+        }
+    };
+
+    let progress = CalibratorStatus::Progress {
+        current: 5,
+        max: 10,
+        message: String::from("Sampling"),
+    };
+    let mut telemetry = Telemetry::default();
+    osc.step(&telemetry, true, Some(&progress));
+    // unchanged progress is not sent again
+    osc.step(&telemetry, true, Some(&progress));
+    receive(2);
+
+    // the calibration went on to continuous mode, which runs into an anomaly
+    telemetry.continuous = true;
+    telemetry.deviation = Some(Deviation {
+        meters: 0.25,
+        degrees: 2.0,
+    });
+    telemetry.anomaly = true;
+    osc.step(&telemetry, true, None);
+    receive(3);
+
+    osc.stopped();
+    receive(1);
+
+    assert_eq!(
+        received,
+        [
+            encode_osc("/motoc/calibration/started", &[]),
+            encode_osc(
+                "/motoc/calibration/progress",
+                &[OscArg::Float(0.5), OscArg::Str(String::from("Sampling"))]
+            ),
+            encode_osc("/motoc/calibration/finished", &[]),
+            encode_osc(
+                "/motoc/continuous/deviation",
+                &[OscArg::Float(0.25), OscArg::Float(2.0)]
+            ),
+            encode_osc("/motoc/anomaly", &[OscArg::Int(1)]),
+            encode_osc("/motoc/anomaly", &[OscArg::Int(0)]),
+        ]
+    );
+}
+
+#[cfg(feature = "dbus")]
+#[test]
+#[ignore = "needs dbus-daemon, run with --ignored"]
+pub fn dbus_service() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    use crate::daemon::IncomingCall;
+    use crate::dbus::{DbusOffset, DbusService, DBUS_NAME, DBUS_PATH};
+
+    // a private bus, so the test neither needs nor touches a session bus
+    let mut bus = Command::new("dbus-daemon")
+        .args(["--session", "--nofork", "--print-address"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("dbus-daemon should be in PATH");
+    let mut address = String::new();
+    BufReader::new(bus.stdout.take().unwrap())
+        .read_line(&mut address)
+        .unwrap();
+    let address = address.trim();
+
+    let (queue, calls) = std::sync::mpsc::channel::<IncomingCall>();
+    let service = DbusService::start_at(address, queue).unwrap();
+    let mut offsets = std::collections::BTreeMap::new();
+    offsets.insert(String::from("STAGE"), TransformD::default());
+    service
+        .publish(&DaemonStatus {
+            calibrator: Some(String::from("floor")),
+            devices: vec![String::from("LHR-ABCDE000")],
+            offsets,
+            ..Default::default()
+        })
+        .unwrap();
+
+    // stands in for the daemon's main loop
+    let main_loop = std::thread::spawn(move || {
+        let call = calls.recv().unwrap();
+        assert_eq!(
+            call.request,
+            DaemonRequest::Reset {
+                id: String::from("STAGE")
+            }
+        );
+        call.reply(Ok(DaemonReply::Message(String::from(
+            "Stage has been reset.",
+        ))));
+        let call = calls.recv().unwrap();
+        assert_eq!(call.request, DaemonRequest::Floor);
+        call.reply(Err(String::from("no hands")));
+    });
+
+    let conn = zbus::blocking::connection::Builder::address(address)
+        .unwrap()
+        .build()
+        .unwrap();
+    let proxy =
+        zbus::blocking::Proxy::new(&conn, DBUS_NAME, DBUS_PATH, "io.github.galister.Motoc1")
+            .unwrap();
+
+    let running: String = proxy.get_property("Running").unwrap();
+    assert_eq!(running, "floor");
+    let devices: Vec<String> = proxy.get_property("Devices").unwrap();
+    assert_eq!(devices, ["LHR-ABCDE000"]);
+    let offsets: std::collections::HashMap<String, DbusOffset> =
+        proxy.get_property("Offsets").unwrap();
+    assert_eq!(offsets["STAGE"], (0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0));
+
+    let message: String = proxy.call("Reset", &("STAGE",)).unwrap();
+    assert_eq!(message, "Stage has been reset.");
+    assert!(proxy.call::<_, _, String>("Floor", &()).is_err());
+    main_loop.join().unwrap();
+
+    // both subscribed before the next publish, so neither can be missed
+    let mut calibrator_changed = proxy.receive_signal("CalibratorChanged").unwrap();
+    let properties = zbus::blocking::fdo::PropertiesProxy::builder(&conn)
+        .destination(DBUS_NAME)
+        .unwrap()
+        .path(DBUS_PATH)
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut properties_changed = properties.receive_properties_changed().unwrap();
+
+    service
+        .publish(&DaemonStatus {
+            calibrator: Some(String::from("recenter STAGE")),
+            message: Some(String::from("Look ahead")),
+            devices: vec![String::from("LHR-ABCDE000")],
+            ..Default::default()
+        })
+        .unwrap();
+
+    // a missing signal fails the test instead of hanging it
+    let (sender, received) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let signal = calibrator_changed.next().unwrap();
+        let args: (String, String) = signal.body().deserialize().unwrap();
+        let changed: Vec<String> = properties_changed
+            .next()
+            .unwrap()
+            .args()
+            .unwrap()
+            .changed_properties()
+            .keys()
+            .map(|name| name.to_string())
+            .collect();
+        let _ = sender.send((args, changed));
+    });
+    let ((running, status), changed) = received
+        .recv_timeout(std::time::Duration::from_secs(5))
+        .expect("CalibratorChanged and PropertiesChanged should be emitted");
+    assert_eq!(running, "recenter STAGE");
+    assert_eq!(status, "Look ahead");
+    assert!(
+        changed.iter().any(|name| name == "Running"),
+        "{:?}",
+        changed
+    );
+    drop(service);
+    let _ = bus.kill();
+    let _ = bus.wait();
+}
+
+#[test]
+pub fn mount_presets() {
+    let presets = builtin_presets();
+    assert!(presets.iter().any(|p| p.name.starts_with("quest3")));
+    assert!(presets.iter().any(|p| p.name.starts_with("pico4")));
+    for preset in presets.iter() {
+        assert!(preset.builtin);
+        // the trackers sit above and behind the eyes, facing forward
+        let offset = preset.offset();
+        assert!(
+            offset.origin.y < 0.0 && offset.origin.z < 0.0,
+            "{}",
+            preset.name
+        );
+        assert_eq!(offset.basis.angle(), 0.0);
+    }
+}
@@ -5,7 +5,7 @@ use nalgebra::{Quaternion, Rotation3, UnitQuaternion, Vector3};
 use openxr as xr;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TransformD {
     pub basis: Rotation3<f64>,
     pub origin: Vector3<f64>,
@@ -0,0 +1,346 @@
+use std::{collections::BTreeMap, sync::atomic::Ordering};
+
+use libmotoc::{
+    Calibrator, CalibratorData, CalibratorStatus, DaemonReply, DaemonRequest, DaemonServer,
+    DaemonStatus, FloorMethod, IncomingCall, RecenterMethod, SampledMethod, Snapshot, TransformD,
+};
+use openxr as xr;
+
+use super::{adjust_offset, change_offset, continue_from_profile, RUNNING};
+
+/// Serves `motoc daemon`: keeps the XR session, and starts and stops calibrators
+/// as requested over the socket, or over D-Bus if enabled.
+pub struct Daemon {
+    server: DaemonServer,
+    config: libmotoc::Config,
+    // what the running calibrator was started as
+    running: Option<String>,
+    // the request that started it
+    active: Option<DaemonRequest>,
+    // continuous mode that a one-shot request took over from, started again once it is done
+    interrupted: Option<DaemonRequest>,
+    #[cfg(feature = "dbus")]
+    dbus: Option<DbusPublisher>,
+}
+
+impl Daemon {
+    pub fn new(config: libmotoc::Config, dbus: bool) -> anyhow::Result<Self> {
+        let server = DaemonServer::bind()?;
+
+        #[cfg(feature = "dbus")]
+        let dbus = match dbus {
+            true => Some(DbusPublisher::new(libmotoc::DbusService::start(
+                server.queue(),
+            )?)),
+            false => None,
+        };
+        #[cfg(not(feature = "dbus"))]
+        if dbus {
+            anyhow::bail!("motoc was built without D-Bus support");
+        }
+
+        Ok(Self {
+            server,
+            config,
+            running: None,
+            active: None,
+            interrupted: None,
+            #[cfg(feature = "dbus")]
+            dbus,
+        })
+    }
+
+    /// Answers the requests that came in since the last frame.
+    pub fn step<G>(
+        &mut self,
+        session: &xr::Session<G>,
+        data: &mut CalibratorData,
+        calibrator: &mut Option<Box<dyn Calibrator>>,
+        status: Option<&CalibratorStatus>,
+    ) -> anyhow::Result<()> {
+        if calibrator.is_none() {
+            self.running = None;
+            self.active = None;
+            if let Some(request) = self.interrupted.take() {
+                log::info!("Going back to {}.", request_label(&request));
+                self.replay(request);
+            }
+        }
+
+        let calls = self.server.poll();
+        #[cfg(feature = "dbus")]
+        let changed = !calls.is_empty();
+
+        for call in calls {
+            log::debug!("Daemon request: {:?}", call.request);
+            let result = self
+                .handle(&call.request, session, data, calibrator, status)
+                .map_err(|e| format!("{:#}", e));
+            if let Err(e) = result.as_ref() {
+                log::warn!("Request failed: {}", e);
+            }
+            call.reply(result);
+        }
+
+        #[cfg(feature = "dbus")]
+        if let Some(dbus) = self.dbus.as_mut() {
+            let running = self.running.clone();
+            dbus.step(changed, || status_of(running, data, status));
+        }
+        Ok(())
+    }
+
+    /// What to ask the daemon again after a reconnect: the profile in continuous mode, if any.
+    pub fn resume_request(&self) -> Option<DaemonRequest> {
+        match self.active.as_ref() {
+            Some(request @ DaemonRequest::Continue { .. }) => Some(request.clone()),
+            _ => self.interrupted.clone(),
+        }
+    }
+
+    /// The running calibrator handed over to another one, e.g. a calibration to continuous mode.
+    pub fn calibrator_replaced(&mut self) {
+        // the new continuous mode stands in for the one that was interrupted
+        self.interrupted = None;
+        if let Some(DaemonRequest::Calibrate {
+            continuous: true,
+            profile,
+            ..
+        }) = self.active.as_ref()
+        {
+            self.active = Some(DaemonRequest::Continue {
+                profile: Some(profile.clone()),
+                fuzzy: false,
+            });
+        }
+    }
+
+    /// The running calibrator failed and was dropped.
+    pub fn calibrator_failed(&mut self) {
+        self.running = None;
+        self.active = None;
+    }
+
+    /// Handles a request as if a client had sent it, without answering anyone.
+    pub fn replay(&self, request: DaemonRequest) {
+        let (call, _) = IncomingCall::new(request);
+        let _ = self.server.queue().send(call);
+    }
+
+    fn handle<G>(
+        &mut self,
+        request: &DaemonRequest,
+        session: &xr::Session<G>,
+        data: &mut CalibratorData,
+        calibrator: &mut Option<Box<dyn Calibrator>>,
+        status: Option<&CalibratorStatus>,
+    ) -> anyhow::Result<DaemonReply> {
+        let mut new: Box<dyn Calibrator> = match request {
+            DaemonRequest::Status => {
+                return Ok(DaemonReply::Status(status_of(
+                    self.running.clone(),
+                    data,
+                    status,
+                )));
+            }
+            DaemonRequest::Stop => {
+                self.active = None;
+                self.interrupted = None;
+                let Some(label) = self.running.take() else {
+                    return Ok(DaemonReply::Message(String::from("Nothing was running.")));
+                };
+                if let Some(mut cal) = calibrator.take() {
+                    cal.finish(data)?;
+                }
+                return Ok(DaemonReply::Message(format!("Stopped {}.", label)));
+            }
+            DaemonRequest::Shutdown => {
+                RUNNING.store(false, Ordering::Relaxed);
+                return Ok(DaemonReply::Message(String::from("Shutting down.")));
+            }
+            DaemonRequest::Adjust {
+                id,
+                relative,
+                yaw,
+                x,
+                y,
+                z,
+            } => {
+                let Some(name) = adjust_offset(data.monado, id, *relative, *yaw, *x, *y, *z)?
+                else {
+                    anyhow::bail!("No such tracking origin: {}", id);
+                };
+                return Ok(DaemonReply::Message(format!("{} has been adjusted.", name)));
+            }
+            DaemonRequest::Calibrate {
+                src,
+                dst,
+                continuous,
+                samples,
+                no_wait,
+                profile,
+            } => {
+                let Some(src_dev) = data.find_device(src) else {
+                    anyhow::bail!("src: no such device: {}", src);
+                };
+                let Some(dst_dev) = data.find_device(dst) else {
+                    anyhow::bail!("dst: no such device: {}", dst);
+                };
+                if data.devices[src_dev].tracking_origin == data.devices[dst_dev].tracking_origin {
+                    anyhow::bail!("both devices are in the same tracking origin");
+                }
+
+                data.tuning = self.config.tuning(Some(profile))?;
+                let method = SampledMethod::new(
+                    src_dev,
+                    dst_dev,
+                    *continuous,
+                    samples.unwrap_or(data.tuning.samples),
+                    profile.clone(),
+                );
+                if *no_wait {
+                    Box::new(method.start_immediately())
+                } else {
+                    Box::new(method)
+                }
+            }
+            DaemonRequest::Continue { profile, fuzzy } => {
+                if let Some(profile) = profile {
+                    data.tuning = self.config.tuning(Some(profile))?;
+                }
+                match continue_from_profile(data, &self.config, profile.as_deref(), *fuzzy)? {
+                    Some(method) => method,
+                    None => {
+                        return Ok(DaemonReply::Message(String::from(
+                            "Applied tracking origin offsets.",
+                        )))
+                    }
+                }
+            }
+            DaemonRequest::Reset { id } => {
+                let Some(name) =
+                    change_offset(data.monado, id, "reset", |_| TransformD::default())?
+                else {
+                    anyhow::bail!("No such tracking origin: {}", id);
+                };
+                return Ok(DaemonReply::Message(format!("{} has been reset.", name)));
+            }
+            DaemonRequest::Recenter { id, height } => Box::new(RecenterMethod::new(id, height)?),
+            DaemonRequest::Floor => Box::new(FloorMethod::new(session)?),
+        };
+
+        // continuous mode comes back once a one-shot request is done, or if it fails to start
+        self.interrupted = self.resume_request();
+        self.running = None;
+        self.active = None;
+        if let Some(mut cal) = calibrator.take() {
+            cal.finish(data)?;
+        }
+        new.init(data)?;
+        *calibrator = Some(new);
+
+        let label = request_label(request);
+        self.running = Some(label.clone());
+        self.active = Some(request.clone());
+        if let DaemonRequest::Continue { .. } = request {
+            self.interrupted = None;
+        }
+        let message = match self.interrupted.as_ref() {
+            Some(previous) => format!(
+                "Started {}. {} resumes once it is done.",
+                label,
+                request_label(previous)
+            ),
+            None => format!("Started {}.", label),
+        };
+        Ok(DaemonReply::Message(message))
+    }
+}
+
+fn request_label(request: &DaemonRequest) -> String {
+    match request {
+        DaemonRequest::Calibrate { src, dst, .. } => format!("calibrate {} → {}", src, dst),
+        DaemonRequest::Continue {
+            profile: Some(profile),
+            ..
+        } => format!("continue {}", profile),
+        DaemonRequest::Continue { profile: None, .. } => String::from("continue"),
+        DaemonRequest::Recenter { id, .. } => format!("recenter {}", id),
+        DaemonRequest::Floor => String::from("floor"),
+        _ => String::new(),
+    }
+}
+
+fn status_of(
+    running: Option<String>,
+    data: &CalibratorData,
+    status: Option<&CalibratorStatus>,
+) -> DaemonStatus {
+    let message = status.map(|status| match status {
+        CalibratorStatus::Spinner { message } => message.clone(),
+        CalibratorStatus::Progress {
+            current,
+            max,
+            message,
+        } => format!("{}/{} {}", current, max, message),
+    });
+
+    let mut offsets = BTreeMap::new();
+    match Snapshot::capture(data.monado) {
+        Ok(snapshot) => {
+            offsets.insert(String::from("STAGE"), snapshot.stage);
+            offsets.insert(String::from("LOCAL"), snapshot.local);
+            for origin in snapshot.origins {
+                offsets.insert(origin.name, origin.offset);
+            }
+        }
+        Err(e) => log::debug!("Could not read offsets: {}", e),
+    }
+
+    DaemonStatus {
+        calibrator: running,
+        message,
+        devices: data
+            .connected_devices()
+            .map(|(_, d)| d.serial.clone())
+            .collect(),
+        tracking_origins: data
+            .tracking_origins
+            .iter()
+            .map(|o| o.name.clone())
+            .collect(),
+        offsets,
+    }
+}
+
+// how often the D-Bus properties are refreshed while nothing is requested
+#[cfg(feature = "dbus")]
+const PUBLISH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[cfg(feature = "dbus")]
+struct DbusPublisher {
+    service: libmotoc::DbusService,
+    last: std::time::Instant,
+}
+
+#[cfg(feature = "dbus")]
+impl DbusPublisher {
+    fn new(service: libmotoc::DbusService) -> Self {
+        Self {
+            service,
+            last: std::time::Instant::now() - PUBLISH_INTERVAL,
+        }
+    }
+
+    /// Refreshes the properties right away if `changed`, otherwise at most every
+    /// `PUBLISH_INTERVAL`.
+    fn step(&mut self, changed: bool, status: impl FnOnce() -> DaemonStatus) {
+        if !changed && self.last.elapsed() < PUBLISH_INTERVAL {
+            return;
+        }
+        self.last = std::time::Instant::now();
+        if let Err(e) = self.service.publish(&status()) {
+            log::warn!("Could not update D-Bus properties: {}", e);
+        }
+    }
+}
@@ -1,34 +1,53 @@
 use std::{
-    collections::HashMap,
     env,
+    io::{self, BufRead, Write},
+    net::SocketAddr,
     process::{Command, ExitCode, Stdio},
     sync::atomic::{AtomicBool, Ordering},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::Context;
 use clap::Parser;
 use env_logger::Env;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use libmonado::{self as mnd, DeviceLogic};
 use nalgebra::{Quaternion, Rotation3, UnitQuaternion};
 use openxr as xr;
-use openxr_mndx_xdev_space::SessionXDevExtensionMNDX;
 
 use libmotoc::TransformD;
-use libmotoc::{vec3, CalibratorData, Device, OffsetType, UNIT};
 use libmotoc::{
-    Calibrator, CalibratorStatus, FloorMethod, OffsetMethod, RecenterMethod, SampledMethod,
-    StepResult,
+    vec3, CalibrationMeta, CalibratorData, DeviceChanges, DeviceInfo, HandJointSource, OffsetType,
+    SaveInfo, SavedCalibration, UNIT,
 };
+use libmotoc::{
+    Calibrator, CalibratorStatus, CaptureMethod, DiscoverMethod, FloorMethod, LevelMethod,
+    OffsetMethod, PointsMethod, RecenterMethod, SampledMethod, StepResult,
+};
+use libmotoc::{DaemonReply, DaemonRequest, MetricsServer, OscOutput, OscSender};
 
+use crate::daemon::Daemon;
 use crate::tui::{Tui, TuiLogBuffer, SPINNER_TICK_CHARS};
+use crate::watch::Watch;
 
+mod daemon;
 mod logbridge;
 mod tui;
+mod watch;
 
 pub static RUNNING: AtomicBool = AtomicBool::new(true);
+pub static TRIGGER: AtomicBool = AtomicBool::new(false);
+
+/// samples used when refining a mount preset
+const REFINE_SAMPLES: u32 = 150;
+
+/// how often devices and tracking origins that came or went are picked up
+const DEVICE_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// the wait before reconnecting to Monado, doubled on each failed attempt
+const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(30);
 
 struct TuiLogger<L> {
     inner: L,
@@ -69,9 +88,22 @@ fn main() -> ExitCode {
         }
     });
 
-    let env_log = env_logger::Builder::from_env(Env::default().default_filter_or("info")).build();
+    let journal = libmotoc::logging_to_journal();
+    let mut env_log = env_logger::Builder::from_env(Env::default().default_filter_or("info"));
+    if journal {
+        // the journal takes the level from the prefix and has its own timestamps
+        env_log.format(|buf, record| {
+            let priority = libmotoc::journal_priority(record.level());
+            writeln!(buf, "<{}>{}", priority, record.args())
+        });
+    }
+    let env_log = env_log.build();
     let max_log_level = env_log.filter();
-    let status = MultiProgress::new();
+    let status = if journal {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    } else {
+        MultiProgress::new()
+    };
     let tui_logs = TuiLogBuffer::new();
     let log = logbridge::LogWrapper::new(status.clone(), env_log);
     log::set_boxed_logger(Box::new(TuiLogger::new(log, tui_logs.clone()))).unwrap();
@@ -79,6 +111,33 @@ fn main() -> ExitCode {
 
     let args = Args::parse();
 
+    if args.via_daemon
+        || matches!(
+            args.command,
+            Subcommands::Daemon {
+                command: Some(_),
+                ..
+            }
+        )
+    {
+        return match forward_to_daemon(&args.command) {
+            Ok(_) => ExitCode::SUCCESS,
+            Err(e) => {
+                log::error!("{:#}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    match handle_offline_subcommands(&args) {
+        Ok(true) => return ExitCode::SUCCESS,
+        Ok(false) => {}
+        Err(e) => {
+            log::error!("{:?}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+
     if args.wait {
         log::info!("Waiting for Monado to become reachable...");
         wait_monado();
@@ -114,11 +173,304 @@ fn main() -> ExitCode {
         }
     }
 
-    if let Err(e) = xr_loop(args, monado, status, tui_logs) {
-        log::error!("{:?}", e);
+    let mut reporting = match Reporting::new(&args) {
+        Ok(reporting) => reporting,
+        Err(e) => {
+            log::error!("{:?}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut monado = monado;
+    let mut command = args.command.clone();
+    let mut replay = None;
+    let mut reconnecting = false;
+    let mut delay = RECONNECT_DELAY_MIN;
+    // systemd restarts the service on failure, so errors must not exit with success
+    let mut exit = ExitCode::SUCCESS;
+    loop {
+        let mut state = LoopState::default();
+        let result = xr_loop(
+            &command,
+            replay.take(),
+            &monado,
+            status.clone(),
+            tui_logs.clone(),
+            &mut reporting,
+            &mut state,
+        );
+        reporting.stopped();
+        if state.started {
+            delay = RECONNECT_DELAY_MIN;
+        }
+
+        let Err(e) = result else {
+            break;
+        };
+        // only go around again if Monado went away, not for mistakes that would repeat
+        let Some(resume) = state.resume.filter(|_| {
+            e.is::<SessionLost>()
+                || (reconnecting && !state.started)
+                || mnd::Monado::auto_connect().is_err()
+        }) else {
+            log::error!("{:?}", e);
+            exit = ExitCode::FAILURE;
+            break;
+        };
+
+        log::warn!("Lost connection to Monado: {:#}", e);
+        let _ = libmotoc::sd_notify("STATUS=Reconnecting to Monado.");
+        let Some(connected) = reconnect_monado(&mut delay) else {
+            break;
+        };
+        log::info!("Resuming {:?}", resume.command);
+        if let Some(request) = resume.request.as_ref() {
+            log::info!("Resuming the daemon's {:?}", request);
+        }
+        monado = connected;
+        command = resume.command;
+        replay = resume.request;
+        reconnecting = true;
+    }
+
+    exit
+}
+
+/// What to pick up again after reconnecting to Monado.
+struct Resume {
+    command: Subcommands,
+    /// the daemon's continuous calibration, sent to it again once it is back
+    request: Option<DaemonRequest>,
+}
+
+impl Resume {
+    /// Commands that keep running can be resumed. One-shot commands are not repeated.
+    fn of(command: &Subcommands) -> Option<Self> {
+        match command {
+            // capturing or sampling again would start over; they resume once they saved, see
+            // `StepResult::Replace`
+            Subcommands::Offset { capture: true, .. }
+            | Subcommands::Offset { refine: true, .. } => None,
+            Subcommands::Offset {
+                preset: Some(_),
+                profile,
+                ..
+            } => Self::of(&Subcommands::Continue {
+                profile: profile.clone(),
+                auto: false,
+                fuzzy: false,
+            }),
+            Subcommands::Continue { .. }
+            | Subcommands::Offset { .. }
+            | Subcommands::Watch { .. }
+            | Subcommands::Monitor
+            | Subcommands::Tui
+            | Subcommands::Daemon { command: None, .. } => Some(Self {
+                command: command.clone(),
+                request: None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Where the calibration state is reported to, kept across reconnects.
+struct Reporting {
+    metrics: Option<MetricsServer>,
+    osc: Option<OscOutput>,
+}
+
+impl Reporting {
+    fn new(args: &Args) -> anyhow::Result<Self> {
+        let metrics = match args.metrics {
+            Some(addr) => Some(
+                MetricsServer::bind(addr)
+                    .with_context(|| format!("Unable to serve metrics on {}", addr))?,
+            ),
+            None => None,
+        };
+        let osc = match args.osc.as_ref() {
+            Some(target) => Some(OscOutput::new(
+                OscSender::connect(target)
+                    .with_context(|| format!("Unable to send OSC to {}", target))?,
+            )),
+            None => None,
+        };
+        Ok(Self { metrics, osc })
+    }
+
+    /// Called every frame.
+    fn step(
+        &mut self,
+        data: &mut CalibratorData,
+        running: bool,
+        status: Option<&CalibratorStatus>,
+    ) {
+        if let Some(osc) = self.osc.as_mut() {
+            osc.step(&data.telemetry, running, status);
+        }
+        if let Some(metrics) = self.metrics.as_mut() {
+            data.observe_tracking();
+            metrics.poll(|| libmotoc::render_metrics(data));
+        }
+    }
+
+    /// `xr_loop` returned, so whatever ran is over.
+    fn stopped(&mut self) {
+        if let Some(osc) = self.osc.as_mut() {
+            osc.stopped();
+        }
     }
+}
 
-    ExitCode::SUCCESS
+/// What `xr_loop` leaves behind for deciding whether to reconnect.
+#[derive(Default)]
+struct LoopState {
+    resume: Option<Resume>,
+    /// whether the XR session got to run
+    started: bool,
+}
+
+/// The XR session or instance went away, e.g. because Monado stopped.
+#[derive(Debug)]
+struct SessionLost(&'static str);
+
+impl std::fmt::Display for SessionLost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for SessionLost {}
+
+/// Waits for Monado to come back, doubling the delay between attempts.
+/// Returns `None` if motoc is asked to stop meanwhile.
+fn reconnect_monado(delay: &mut Duration) -> Option<mnd::Monado> {
+    loop {
+        log::info!("Reconnecting in {}s...", delay.as_secs());
+        let until = Instant::now() + *delay;
+        while Instant::now() < until {
+            if !RUNNING.load(Ordering::Relaxed) {
+                log::info!("Received shutdown signal.");
+                return None;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        *delay = (*delay * 2).min(RECONNECT_DELAY_MAX);
+
+        match mnd::Monado::auto_connect() {
+            Ok(monado) => {
+                log::info!("Reconnected to Monado.");
+                return Some(monado);
+            }
+            Err(_) => log::info!("Monado is not reachable yet."),
+        }
+    }
+}
+
+/// Sends the command to a running `motoc daemon` instead of running it here.
+fn forward_to_daemon(command: &Subcommands) -> anyhow::Result<()> {
+    let request = match command {
+        Subcommands::Daemon { command: None, .. } => {
+            anyhow::bail!("the daemon cannot be started via the daemon")
+        }
+        Subcommands::Daemon {
+            command: Some(command),
+            ..
+        } => match command {
+            DaemonCommand::Status => DaemonRequest::Status,
+            DaemonCommand::Stop => DaemonRequest::Stop,
+            DaemonCommand::Shutdown => DaemonRequest::Shutdown,
+        },
+        Subcommands::Calibrate {
+            src,
+            dst,
+            r#continue,
+            samples,
+            no_wait,
+            profile,
+        } => DaemonRequest::Calibrate {
+            src: src.clone(),
+            dst: dst.clone(),
+            continuous: *r#continue,
+            samples: *samples,
+            no_wait: *no_wait,
+            profile: profile.clone(),
+        },
+        Subcommands::Continue {
+            profile,
+            auto,
+            fuzzy,
+        } => DaemonRequest::Continue {
+            profile: (!auto).then(|| profile.clone()),
+            fuzzy: *fuzzy,
+        },
+        Subcommands::Adjust {
+            id,
+            relative,
+            yaw,
+            x,
+            y,
+            z,
+        } => DaemonRequest::Adjust {
+            id: id.clone(),
+            relative: *relative,
+            yaw: *yaw,
+            x: *x,
+            y: *y,
+            z: *z,
+        },
+        Subcommands::Recenter { id, height } => DaemonRequest::Recenter {
+            id: id.clone(),
+            height: height.clone(),
+        },
+        Subcommands::Floor => DaemonRequest::Floor,
+        Subcommands::Reset { id } => DaemonRequest::Reset { id: id.clone() },
+        _ => anyhow::bail!("this command cannot run via the daemon"),
+    };
+
+    match libmotoc::call_daemon(&request)? {
+        DaemonReply::Message(message) => println!("{}", message),
+        DaemonReply::Status(status) => {
+            println!(
+                "Running:  {}",
+                status.calibrator.as_deref().unwrap_or("nothing")
+            );
+            if let Some(message) = status.message {
+                println!("Status:   {}", message);
+            }
+            println!("Origins:  {}", status.tracking_origins.join(", "));
+            println!("Devices:  {}", status.devices.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Tells systemd that the service is up, see `motoc service install`.
+fn notify_ready(status: &str) {
+    if let Err(e) = libmotoc::sd_notify(&format!("READY=1\nSTATUS={}", status)) {
+        log::warn!("Could not notify systemd: {}", e);
+    }
+}
+
+/// Updates the status line `systemctl status` shows.
+fn notify_status(status: &str) {
+    if let Err(e) = libmotoc::sd_notify(&format!("STATUS={}", status)) {
+        log::warn!("Could not notify systemd: {}", e);
+    }
+}
+
+/// Runs `systemctl --user` with the given arguments.
+fn systemctl(args: &[&str]) -> anyhow::Result<()> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .context("Could not run systemctl")?;
+    if !status.success() {
+        anyhow::bail!("systemctl --user {} failed", args.join(" "));
+    }
+    Ok(())
 }
 
 fn wait_monado() {
@@ -146,6 +498,465 @@ fn wait_monado() {
     }
 }
 
+/// Confirms the current calibrator step whenever Enter is pressed.
+fn spawn_trigger_reader() {
+    thread::spawn(|| {
+        for line in io::stdin().lock().lines() {
+            if line.is_err() {
+                break;
+            }
+            TRIGGER.store(true, Ordering::Relaxed);
+        }
+    });
+}
+
+/// Commands that only touch local files and do not need Monado.
+fn handle_offline_subcommands(args: &Args) -> anyhow::Result<bool> {
+    match &args.command {
+        Subcommands::Presets => {
+            for preset in libmotoc::load_presets()? {
+                let origin = if preset.builtin { "" } else { " (user)" };
+                println!("{}{}", preset.name, origin);
+                if !preset.description.is_empty() {
+                    println!(" │ {}", preset.description);
+                }
+                println!(" └ {}", preset.offset());
+            }
+            Ok(true)
+        }
+        Subcommands::ImportSvc {
+            file,
+            profile,
+            src,
+            dst,
+        } => {
+            let json = std::fs::read_to_string(file)
+                .with_context(|| format!("Could not read {}", file))?;
+            let svc = libmotoc::SvcProfile::from_json(&json)?;
+            let saved = svc.into_saved(src.clone(), dst.clone());
+            log::info!("Importing {} → {}: {}", saved.src, saved.dst, saved.offset);
+            libmotoc::add_to_profile(profile, saved)?;
+            log::info!("Saved to profile '{}'.", profile);
+            Ok(true)
+        }
+        Subcommands::ExportSvc {
+            profile,
+            dst,
+            output,
+        } => {
+            let saved = libmotoc::read_profile(profile)?;
+            let mut origins = saved
+                .offsets
+                .iter()
+                .filter(|o| o.offset_type == OffsetType::TrackingOrigin)
+                .filter(|o| dst.as_ref().is_none_or(|dst| *dst == o.dst));
+            let Some(entry) = origins.next() else {
+                anyhow::bail!(
+                    "Profile '{}' has no matching tracking origin offset.",
+                    profile
+                );
+            };
+            if origins.next().is_some() {
+                log::warn!(
+                    "Profile has several tracking origin offsets, exporting {}. Pick one with --dst.",
+                    entry.dst
+                );
+            }
+
+            let json = libmotoc::SvcProfile::from_offset(&entry.src, &entry.dst, &entry.offset)
+                .to_json()?;
+            match output {
+                Some(path) => {
+                    std::fs::write(path, json)
+                        .with_context(|| format!("Could not write {}", path))?;
+                    log::info!("Exported {} to {}.", entry.dst, path);
+                }
+                None => println!("{}", json),
+            }
+            Ok(true)
+        }
+        Subcommands::ExportMonado {
+            profile,
+            write,
+            config,
+        } => {
+            let saved = libmotoc::read_profile(profile)?;
+            let mut overrides = Vec::new();
+            for entry in saved.offsets.iter() {
+                match libmotoc::tracking_override(entry) {
+                    Some(o) => overrides.push(o),
+                    None => log::warn!(
+                        "Skipping the tracking origin offset for {}, Monado's config has no equivalent. Use `motoc continue` to apply it.",
+                        entry.dst
+                    ),
+                }
+            }
+            if overrides.is_empty() {
+                anyhow::bail!("Profile '{}' has no device offsets to export.", profile);
+            }
+            log::warn!("Monado will report the HMD at the tracker's pose instead of tracking it. Other devices of the HMD's tracking origin, e.g. its own controllers, are not moved with it.");
+
+            let path = match config {
+                Some(path) => path.into(),
+                None => libmotoc::monado_config_path()?,
+            };
+            let mut merged = libmotoc::read_monado_config(&path)?;
+            libmotoc::merge_tracking_overrides(&mut merged, &overrides)?;
+
+            if *write {
+                libmotoc::write_monado_config(&path, &merged)?;
+                log::info!(
+                    "Wrote {} tracking overrides to {}. Restart Monado to use them.",
+                    overrides.len(),
+                    path.to_string_lossy()
+                );
+            } else {
+                println!("{}", libmotoc::format_monado_config(&merged)?);
+            }
+            Ok(true)
+        }
+        Subcommands::History { count } => {
+            let entries = libmotoc::read_journal()?;
+            let undoable: Vec<u64> = libmotoc::undoable(&entries).iter().map(|e| e.id).collect();
+            for entry in entries
+                .iter()
+                .rev()
+                .filter(|e| !libmotoc::is_completed(&entries, e))
+                .take(*count)
+            {
+                let delta = entry.previous.inverse() * entry.new;
+                let state = match entry.reverts {
+                    Some(id) => format!("undo of #{}", id),
+                    None if !undoable.contains(&entry.id) => String::from("undone"),
+                    None if entry.ongoing => String::from("interrupted"),
+                    None => String::new(),
+                };
+                let change = if entry.ongoing {
+                    String::from("Δ unknown")
+                } else {
+                    format!(
+                        "Δpos {:.3}m  Δrot {:.2}°",
+                        delta.origin.norm(),
+                        delta.basis.angle().to_degrees()
+                    )
+                };
+                println!(
+                    "#{:<4} {}  {:<16} {:<20} {}  {}",
+                    entry.id,
+                    format_created(entry.time),
+                    entry.source,
+                    entry.target.to_string(),
+                    change,
+                    state
+                );
+            }
+            Ok(true)
+        }
+        Subcommands::Service { command } => {
+            match command {
+                ServiceCommand::Install {
+                    profile,
+                    watch,
+                    daemon,
+                    force,
+                    no_enable,
+                } => {
+                    let mode = if *daemon {
+                        libmotoc::ServiceMode::Daemon
+                    } else if *watch {
+                        libmotoc::ServiceMode::Watch {
+                            profile: profile.clone(),
+                        }
+                    } else {
+                        libmotoc::ServiceMode::Continue {
+                            profile: profile.clone().unwrap_or_else(|| String::from("last")),
+                        }
+                    };
+                    let exe = env::current_exe().context("Could not determine current exe")?;
+                    let path = libmotoc::write_service(&exe, &mode, *force)?;
+                    println!("Wrote {}", path.display());
+
+                    if *no_enable {
+                        println!(
+                            "Start it with: systemctl --user enable --now {}",
+                            libmotoc::SERVICE_NAME
+                        );
+                    } else {
+                        systemctl(&["daemon-reload"])?;
+                        systemctl(&["enable", libmotoc::SERVICE_NAME])?;
+                        // the service is only ready once Monado is up, so don't wait for that
+                        systemctl(&["restart", "--no-block", libmotoc::SERVICE_NAME])?;
+                        println!(
+                            "{} is enabled and starting. See `motoc service status`.",
+                            libmotoc::SERVICE_NAME
+                        );
+                    }
+                }
+                ServiceCommand::Uninstall => {
+                    if let Err(e) = systemctl(&["disable", "--now", libmotoc::SERVICE_NAME]) {
+                        log::warn!("{:#}", e);
+                    }
+                    if libmotoc::remove_service()? {
+                        systemctl(&["daemon-reload"])?;
+                        println!("Removed {}", libmotoc::service_path()?.display());
+                    } else {
+                        println!("{} is not installed.", libmotoc::SERVICE_NAME);
+                    }
+                }
+                ServiceCommand::Status => {
+                    let path = libmotoc::service_path()?;
+                    if !path.exists() {
+                        println!("{} is not installed.", libmotoc::SERVICE_NAME);
+                        return Ok(true);
+                    }
+                    println!("# {}", path.display());
+                    // systemctl status exits non-zero for inactive units
+                    let _ = systemctl(&["--no-pager", "status", libmotoc::SERVICE_NAME]);
+                }
+            }
+            Ok(true)
+        }
+        Subcommands::Config { profile } => {
+            let tuning = libmotoc::Config::load()?.tuning(profile.as_deref())?;
+            println!("# {}", libmotoc::config_path()?.display());
+            print!("{}", libmotoc::format_tuning(&tuning)?);
+            Ok(true)
+        }
+        Subcommands::Alias { command } => {
+            let mut aliases = libmotoc::Aliases::load()?;
+            match command {
+                AliasCommand::List => {
+                    for (kind, group) in [
+                        (libmotoc::NameKind::Origin, &aliases.origins),
+                        (libmotoc::NameKind::Device, &aliases.devices),
+                    ] {
+                        for (name, names) in group.iter() {
+                            println!("{} \"{}\"", kind.label(), name);
+                            println!(" └ {}", names.join(", "));
+                        }
+                    }
+                }
+                AliasCommand::Add {
+                    name,
+                    alias,
+                    device,
+                } => {
+                    aliases.add(alias_kind(*device), name, alias);
+                    aliases.save()?;
+                    log::info!("\"{}\" is now also known as \"{}\".", name, alias);
+                }
+                AliasCommand::Remove { alias, device } => {
+                    if !aliases.remove(alias_kind(*device), alias) {
+                        log::error!("No such alias: {}", alias);
+                        return Ok(true);
+                    }
+                    aliases.save()?;
+                    log::info!("Removed alias \"{}\".", alias);
+                }
+            }
+            Ok(true)
+        }
+        Subcommands::Profile { command } => {
+            match command {
+                ProfileCommand::List => {
+                    for (name, profile) in libmotoc::list_profiles()? {
+                        match profile {
+                            Ok(profile) => {
+                                println!("{} ({})", name, format_created(profile.created()));
+                                for (i, saved) in profile.offsets.iter().enumerate() {
+                                    let tree = if i + 1 == profile.offsets.len() {
+                                        '└'
+                                    } else {
+                                        '├'
+                                    };
+                                    println!(
+                                        " {} {}, {}",
+                                        tree,
+                                        profile_pair(saved),
+                                        profile_quality(&saved.meta)
+                                    );
+                                }
+                            }
+                            Err(e) => {
+                                println!("{} (unreadable)", name);
+                                println!(" └ {}", e);
+                            }
+                        }
+                    }
+                }
+                ProfileCommand::Show { name } => {
+                    let profile = libmotoc::read_profile(name)?;
+                    println!("Profile:  {}", name);
+                    for saved in profile.offsets.iter() {
+                        let meta = &saved.meta;
+                        let kind = match saved.offset_type {
+                            OffsetType::TrackingOrigin => "tracking origin offset",
+                            OffsetType::Device => "device offset (continuous)",
+                        };
+                        println!();
+                        println!("Type:     {}", kind);
+                        println!("Created:  {}", format_created(meta.created));
+                        if !meta.motoc_version.is_empty() {
+                            println!("Version:  motoc {}", meta.motoc_version);
+                        }
+                        println!("Source:   {}", saved.src);
+                        println!("Target:   {}", saved.dst);
+                        for (label, dev) in
+                            [("SRC dev", &meta.src_device), ("DST dev", &meta.dst_device)]
+                        {
+                            if let Some(dev) = dev {
+                                println!("{}:  {} ({})", label, dev.serial, dev.name);
+                            }
+                        }
+                        for (label, origin) in
+                            [("SRC org", &meta.src_origin), ("DST org", &meta.dst_origin)]
+                        {
+                            if let Some(origin) = origin {
+                                println!("{}:  {}", label, origin);
+                            }
+                        }
+                        println!("Quality:  {}", profile_quality(meta));
+                        println!("Offset:   {}", saved.offset);
+                    }
+                }
+                ProfileCommand::Delete { name } => {
+                    libmotoc::delete_profile(name)?;
+                    log::info!("Deleted profile '{}'.", name);
+                }
+                ProfileCommand::Rename { from, to } => {
+                    libmotoc::rename_profile(from, to)?;
+                    log::info!("Renamed profile '{}' to '{}'.", from, to);
+                }
+                ProfileCommand::Copy { from, to } => {
+                    libmotoc::copy_profile(from, to)?;
+                    log::info!("Copied profile '{}' to '{}'.", from, to);
+                }
+                ProfileCommand::Rebind { name, from, to } => {
+                    let mut profile = libmotoc::read_profile(name)?;
+                    let changed = profile.rebind(from, to);
+                    if changed == 0 {
+                        return Err(libmotoc::Error::NameNotInProfile(from.clone()).into());
+                    }
+                    libmotoc::write_profile(name, &profile)?;
+                    log::info!(
+                        "Replaced \"{}\" with \"{}\" in {} offsets of profile '{}'.",
+                        from,
+                        to,
+                        changed,
+                        name
+                    );
+                }
+            }
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn alias_kind(device: bool) -> libmotoc::NameKind {
+    if device {
+        libmotoc::NameKind::Device
+    } else {
+        libmotoc::NameKind::Origin
+    }
+}
+
+/// Sets or moves the offset of STAGE, LOCAL or a tracking origin by id or name.
+/// Returns what was adjusted, or `None` if there is no such tracking origin.
+fn adjust_offset(
+    monado: &mnd::Monado,
+    id: &str,
+    relative: bool,
+    yaw: Option<f64>,
+    x: Option<f64>,
+    y: Option<f64>,
+    z: Option<f64>,
+) -> anyhow::Result<Option<String>> {
+    change_offset(monado, id, "adjust", |current| {
+        let mut offset = if relative {
+            current
+        } else {
+            TransformD::default()
+        };
+        offset.origin += vec3(x.unwrap_or(0.0), y.unwrap_or(0.0), z.unwrap_or(0.0));
+        offset.basis = Rotation3::from_axis_angle(&UNIT.YU, yaw.unwrap_or(0.0)) * offset.basis;
+        offset
+    })
+}
+
+/// Replaces the offset of STAGE, LOCAL or a tracking origin by id or name with
+/// `change` applied to the current one. Returns what was changed, or `None` if
+/// there is no such tracking origin.
+fn change_offset(
+    monado: &mnd::Monado,
+    id: &str,
+    source: &str,
+    change: impl Fn(TransformD) -> TransformD,
+) -> anyhow::Result<Option<String>> {
+    let id_lower = id.to_lowercase();
+
+    let ref_space_type = match id_lower.as_str() {
+        "stage" => Some(mnd::ReferenceSpaceType::Stage),
+        "local" => Some(mnd::ReferenceSpaceType::Local),
+        _ => None,
+    };
+
+    if let Some(ref_space_type) = ref_space_type {
+        let current = monado
+            .get_reference_space_offset(ref_space_type)
+            .context("Could not get reference space offset")?
+            .into();
+        libmotoc::set_space_offset(monado, ref_space_type, change(current), source)?;
+        return Ok(Some(format!("{:?}", ref_space_type)));
+    }
+
+    let maybe_id_num: Option<u32> = id.parse().ok();
+    for to in monado.tracking_origins()?.into_iter() {
+        if maybe_id_num.is_none_or(|x| x != to.id) && id_lower != to.name.to_lowercase() {
+            continue;
+        }
+
+        let current = to.get_offset()?.into();
+        libmotoc::set_origin_offset(&to, change(current), source)?;
+        return Ok(Some(to.name.clone()));
+    }
+    Ok(None)
+}
+
+fn format_created(created: u64) -> String {
+    if created == 0 {
+        return String::from("unknown date");
+    }
+    match jiff::Timestamp::from_second(created as i64) {
+        Ok(ts) => ts
+            .to_zoned(jiff::tz::TimeZone::system())
+            .strftime("%Y-%m-%d %H:%M")
+            .to_string(),
+        Err(_) => String::from("invalid date"),
+    }
+}
+
+fn profile_pair(profile: &SavedCalibration) -> String {
+    let label = |dev: &Option<DeviceInfo>, fallback: &str| match dev {
+        Some(dev) => format!("{} ({})", dev.serial, dev.name),
+        None => fallback.to_string(),
+    };
+    format!(
+        "{} → {}",
+        label(&profile.meta.src_device, &profile.src),
+        label(&profile.meta.dst_device, &profile.dst)
+    )
+}
+
+fn profile_quality(meta: &CalibrationMeta) -> String {
+    match (meta.samples, meta.rms_error) {
+        (Some(samples), Some(rms)) => format!("{} samples, RMS error {:.3}m", samples, rms),
+        (Some(samples), None) => format!("{} samples", samples),
+        (None, Some(rms)) => format!("RMS error {:.3}m", rms),
+        (None, None) => String::from("no quality figures"),
+    }
+}
+
 fn handle_non_xr_subcommands(args: &Args, monado: &mnd::Monado) -> anyhow::Result<bool> {
     match args.command {
         Subcommands::NumDevices => {
@@ -224,16 +1035,20 @@ fn handle_non_xr_subcommands(args: &Args, monado: &mnd::Monado) -> anyhow::Resul
         Subcommands::Reset { ref id } => {
             match id.to_lowercase().as_str() {
                 "stage" => {
-                    monado.set_reference_space_offset(
+                    libmotoc::set_space_offset(
+                        monado,
                         mnd::ReferenceSpaceType::Stage,
-                        TransformD::default().into(),
+                        TransformD::default(),
+                        "reset",
                     )?;
                     println!("STAGE has been reset!");
                 }
                 "local" => {
-                    monado.set_reference_space_offset(
+                    libmotoc::set_space_offset(
+                        monado,
                         mnd::ReferenceSpaceType::Local,
-                        TransformD::default().into(),
+                        TransformD::default(),
+                        "reset",
                     )?;
                     println!("LOCAL has been reset!");
                 }
@@ -250,9 +1065,9 @@ fn handle_non_xr_subcommands(args: &Args, monado: &mnd::Monado) -> anyhow::Resul
                         if to.id != id {
                             continue;
                         }
-                        match to.set_offset(TransformD::default().into()) {
+                        match libmotoc::set_origin_offset(&to, TransformD::default(), "reset") {
                             Ok(_) => println!("{} has been reset.", to.name),
-                            Err(e) => println!("Could not reset due to libmonado error: {:?}", e),
+                            Err(e) => println!("Could not reset: {}", e),
                         }
                         return Ok(true);
                     }
@@ -269,57 +1084,71 @@ fn handle_non_xr_subcommands(args: &Args, monado: &mnd::Monado) -> anyhow::Resul
             y,
             z,
         } => {
-            let id_lower = id.to_lowercase();
-
-            let ref_space_type = match id_lower.as_str() {
-                "stage" => Some(mnd::ReferenceSpaceType::Stage),
-                "local" => Some(mnd::ReferenceSpaceType::Local),
-                _ => None,
-            };
-
-            if let Some(ref_space_type) = ref_space_type {
-                let mut offset = if relative {
-                    monado
-                        .get_reference_space_offset(ref_space_type)
-                        .context("Could not get reference space offset")?
-                        .into()
-                } else {
-                    TransformD::default()
-                };
-                offset.origin += vec3(x.unwrap_or(0.0), y.unwrap_or(0.0), z.unwrap_or(0.0));
-                offset.basis =
-                    Rotation3::from_axis_angle(&UNIT.YU, yaw.unwrap_or(0.0)) * offset.basis;
+            match adjust_offset(monado, id, relative, yaw, x, y, z) {
+                Ok(Some(name)) => println!("{} has been adjusted.", name),
+                Ok(None) => {}
+                Err(e) => println!("Could not adjust: {}", e),
+            }
 
-                match monado.set_reference_space_offset(ref_space_type, offset.into()) {
-                    Ok(_) => println!("{:?} has been adjusted.", ref_space_type),
-                    Err(e) => println!("Could not adjust due to libmonado error: {:?}", e),
+            Ok(true)
+        }
+        Subcommands::Snapshot { ref command } => {
+            match command {
+                SnapshotCommand::Save { name, force } => {
+                    let snapshot = libmotoc::Snapshot::capture(monado)?;
+                    libmotoc::write_snapshot(name, &snapshot, *force)?;
+                    log::info!(
+                        "Saved STAGE, LOCAL and {} tracking origins to snapshot '{}'.",
+                        snapshot.origins.len(),
+                        name
+                    );
                 }
-            } else {
-                let maybe_id_num: Option<u32> = id.parse().ok();
-                for to in monado.tracking_origins()?.into_iter() {
-                    if maybe_id_num.is_none_or(|x| x != to.id) && id_lower != to.name.to_lowercase()
-                    {
-                        continue;
+                SnapshotCommand::Restore { name } => {
+                    let snapshot = libmotoc::read_snapshot(name)?;
+                    for missing in snapshot.restore(monado)? {
+                        log::warn!("Tracking origin not present, skipped: {}", missing);
                     }
-
-                    let mut offset = if relative {
-                        to.get_offset()?.into()
-                    } else {
-                        TransformD::default()
-                    };
-
-                    offset.origin += vec3(x.unwrap_or(0.0), y.unwrap_or(0.0), z.unwrap_or(0.0));
-                    offset.basis =
-                        Rotation3::from_axis_angle(&UNIT.YU, yaw.unwrap_or(0.0)) * offset.basis;
-
-                    match to.set_offset(offset.into()) {
-                        Ok(_) => println!("{} has been adjusted.", to.name),
-                        Err(e) => println!("Could not adjust due to libmonado error: {:?}", e),
+                    log::info!("Restored snapshot '{}'.", name);
+                }
+                SnapshotCommand::Diff { name } => {
+                    let snapshot = libmotoc::read_snapshot(name)?;
+                    let live = libmotoc::Snapshot::capture(monado)?;
+                    println!(
+                        "Snapshot '{}' ({}) against the current offsets:",
+                        name,
+                        format_created(snapshot.created)
+                    );
+                    for diff in snapshot.diff(&live) {
+                        match (diff.distance(), diff.angle()) {
+                            (Some(distance), Some(angle)) => println!(
+                                "{:<24} Δpos {:.3}m  Δrot {:.2}°",
+                                diff.name, distance, angle
+                            ),
+                            _ if diff.live.is_none() => {
+                                println!("{:<24} not present anymore", diff.name)
+                            }
+                            _ => println!("{:<24} not in snapshot", diff.name),
+                        }
                     }
-                    break;
                 }
             }
-
+            Ok(true)
+        }
+        Subcommands::Undo { count } => {
+            let undone = libmotoc::undo(monado, count)?;
+            if undone.is_empty() {
+                println!("Nothing to undo.");
+            }
+            for entry in undone {
+                println!(
+                    "Undid #{} ({}, {}): {} is back to {}",
+                    entry.id,
+                    entry.source,
+                    format_created(entry.time),
+                    entry.target,
+                    entry.previous
+                );
+            }
             Ok(true)
         }
         Subcommands::Check => Ok(true),
@@ -398,11 +1227,19 @@ fn update_status(
 }
 
 fn xr_loop(
-    args: Args,
-    monado: mnd::Monado,
+    command: &Subcommands,
+    replay: Option<DaemonRequest>,
+    monado: &mnd::Monado,
     status: MultiProgress,
     tui_logs: TuiLogBuffer,
+    reporting: &mut Reporting,
+    state: &mut LoopState,
 ) -> anyhow::Result<()> {
+    state.resume = Resume::of(command).map(|resume| Resume {
+        request: replay.clone(),
+        ..resume
+    });
+
     let (instance, system) = libmotoc::xr_init()?;
 
     let actions = instance.create_action_set("motoc", "MoToC", 0)?;
@@ -424,6 +1261,9 @@ fn xr_loop(
     let mut calibrator: Option<Box<dyn Calibrator>> = None;
     let mut calibrator_status: Option<CalibratorStatus>;
     let mut cli_status = None;
+    let mut daemon = None;
+    let mut watch = None;
+    let mut last_refresh = Instant::now();
 
     'main_loop: loop {
         'event_loop: while let Some(event) = instance.poll_event(&mut events)? {
@@ -439,9 +1279,27 @@ fn xr_loop(
                             continue 'event_loop;
                         }
 
-                        let mut data = load_calibrator_data(&session, &monado)?;
+                        let config = libmotoc::Config::load()?;
+                        let tuning = config.tuning(command.profile())?;
+                        let mut data = CalibratorData::new(&session, monado, tuning)?;
+                        state.started = true;
 
-                        match args.command {
+                        match *command {
+                            Subcommands::Daemon {
+                                command: None,
+                                dbus,
+                            } => {
+                                let new = Daemon::new(config, dbus)?;
+                                notify_ready("Waiting for commands.");
+                                if let Some(request) = replay.clone() {
+                                    new.replay(request);
+                                }
+                                daemon = Some(new);
+                            }
+                            Subcommands::Watch { ref profile, fuzzy } => {
+                                watch = Some(Watch::new(config, profile.clone(), fuzzy));
+                                notify_ready("Waiting for the profile's devices.");
+                            }
                             Subcommands::Monitor | Subcommands::Tui => {
                                 let mut ui = Tui::new(tui_logs.clone());
                                 ui.init()?;
@@ -457,7 +1315,12 @@ fn xr_loop(
                                 y,
                                 z,
                                 lerp,
+                                capture,
+                                ref preset,
+                                refine,
+                                ref profile,
                             } => {
+                                let lerp = lerp.unwrap_or(data.tuning.offset_lerp_factor);
                                 let Some(src_dev) = data.find_device(src) else {
                                     log::error!("src: no such device: {}", &src);
                                     break 'main_loop;
@@ -474,27 +1337,158 @@ fn xr_loop(
                                     break 'main_loop;
                                 }
 
-                                calibrator = Some(Box::new({
-                                    let mut c = OffsetMethod::new(
-                                        src_dev,
-                                        dst_dev,
-                                        vec3(
-                                            pitch.unwrap_or(0.0),
-                                            yaw.unwrap_or(0.0),
-                                            roll.unwrap_or(0.0),
-                                        ),
-                                        vec3(x.unwrap_or(0.0), y.unwrap_or(0.0), z.unwrap_or(0.0)),
-                                        lerp,
+                                if capture {
+                                    spawn_trigger_reader();
+                                    calibrator = Some(Box::new({
+                                        let mut c = CaptureMethod::new(
+                                            src_dev,
+                                            dst_dev,
+                                            lerp,
+                                            profile.clone(),
+                                        );
+                                        c.init(&mut data)?;
+                                        c
+                                    }));
+                                } else if let Some(preset) = preset {
+                                    let preset = libmotoc::find_preset(preset)?;
+                                    log::info!(
+                                        "Using mount preset {}: {}",
+                                        preset.name,
+                                        preset.description
                                     );
-                                    c.init(&mut data)?;
-                                    c
-                                }));
+
+                                    if refine {
+                                        calibrator = Some(Box::new({
+                                            let mut c = SampledMethod::new(
+                                                src_dev,
+                                                dst_dev,
+                                                true,
+                                                REFINE_SAMPLES,
+                                                profile.clone(),
+                                            )
+                                            .refine(preset.offset());
+                                            c.init(&mut data)?;
+                                            c
+                                        }));
+                                    } else {
+                                        match data.save_calibration(
+                                            profile,
+                                            src_dev,
+                                            dst_dev,
+                                            preset.offset(),
+                                            OffsetType::Device,
+                                            SaveInfo::default(),
+                                        ) {
+                                            Ok(_) => log::info!(
+                                                "Saved preset offset to profile '{}'.",
+                                                profile
+                                            ),
+                                            Err(e) => {
+                                                log::warn!("Could not save calibration: {}", e)
+                                            }
+                                        }
+                                        calibrator = Some(Box::new({
+                                            let mut c = OffsetMethod::new_internal(
+                                                src_dev,
+                                                dst_dev,
+                                                preset.offset(),
+                                                lerp,
+                                            );
+                                            c.init(&mut data)?;
+                                            c
+                                        }));
+                                    }
+                                } else {
+                                    calibrator = Some(Box::new({
+                                        let mut c = OffsetMethod::new(
+                                            src_dev,
+                                            dst_dev,
+                                            vec3(
+                                                pitch.unwrap_or(0.0),
+                                                yaw.unwrap_or(0.0),
+                                                roll.unwrap_or(0.0),
+                                            ),
+                                            vec3(
+                                                x.unwrap_or(0.0),
+                                                y.unwrap_or(0.0),
+                                                z.unwrap_or(0.0),
+                                            ),
+                                            lerp,
+                                        );
+                                        c.init(&mut data)?;
+                                        c
+                                    }));
+                                }
                             }
                             Subcommands::Calibrate {
                                 ref src,
                                 ref dst,
                                 r#continue: maintain,
                                 samples,
+                                no_wait,
+                                ref profile,
+                            } => {
+                                let Some(dst_dev) = data.find_device(dst) else {
+                                    log::error!("dst: no such device: {}", &dst);
+                                    break 'main_loop;
+                                };
+
+                                let mut method = if let Some(hand_src) =
+                                    HandJointSource::parse(src)?
+                                {
+                                    if maintain {
+                                        log::error!(
+                                            "continuous mode is not available with a hand joint source"
+                                        );
+                                        break 'main_loop;
+                                    }
+
+                                    let hand_origin = data.hand_tracking_origin(hand_src.hand)?;
+                                    if hand_origin == data.devices[dst_dev].tracking_origin {
+                                        log::error!("both devices are in the same tracking origin");
+                                        break 'main_loop;
+                                    }
+
+                                    SampledMethod::with_hand_source(
+                                        &session,
+                                        hand_src,
+                                        hand_origin,
+                                        dst_dev,
+                                        samples.unwrap_or(data.tuning.samples),
+                                        profile.clone(),
+                                    )?
+                                } else {
+                                    let Some(src_dev) = data.find_device(src) else {
+                                        log::error!("src: no such device: {}", &src);
+                                        break 'main_loop;
+                                    };
+
+                                    if data.devices[src_dev].tracking_origin
+                                        == data.devices[dst_dev].tracking_origin
+                                    {
+                                        log::error!("both devices are in the same tracking origin");
+                                        break 'main_loop;
+                                    }
+
+                                    SampledMethod::new(
+                                        src_dev,
+                                        dst_dev,
+                                        maintain,
+                                        samples.unwrap_or(data.tuning.samples),
+                                        profile.clone(),
+                                    )
+                                };
+
+                                if no_wait {
+                                    method = method.start_immediately();
+                                }
+                                method.init(&mut data)?;
+                                calibrator = Some(Box::new(method));
+                            }
+                            Subcommands::Points {
+                                ref src,
+                                ref dst,
+                                points,
                                 ref profile,
                             } => {
                                 let Some(src_dev) = data.find_device(src) else {
@@ -513,87 +1507,76 @@ fn xr_loop(
                                     break 'main_loop;
                                 }
 
+                                spawn_trigger_reader();
                                 calibrator = Some(Box::new({
-                                    let mut c = SampledMethod::new(
+                                    let mut c = PointsMethod::new(
                                         src_dev,
                                         dst_dev,
-                                        maintain,
-                                        samples.unwrap_or(500),
+                                        points.unwrap_or(4),
                                         profile.clone(),
                                     );
                                     c.init(&mut data)?;
                                     c
                                 }));
                             }
-                            Subcommands::Continue { ref profile } => {
-                                let Ok(last) = data.load_calibration(profile.as_str()) else {
-                                    log::error!(
-                                        "Could not load calibration for profile '{}'. Did you mean to calibrate first?",
-                                        profile
+                            Subcommands::Discover {
+                                seconds,
+                                calibrate,
+                                r#continue: maintain,
+                                samples,
+                                ref profile,
+                            } => {
+                                let mut method = DiscoverMethod::new(seconds);
+                                if calibrate {
+                                    method = method.then_calibrate(
+                                        maintain,
+                                        samples.unwrap_or(data.tuning.samples),
+                                        profile.clone(),
                                     );
-                                    break 'main_loop;
-                                };
-
-                                match last.offset_type {
-                                    OffsetType::TrackingOrigin => {
-                                        let src_transform = if let Some(src_origin) = data
-                                            .tracking_origins
-                                            .iter()
-                                            .find(|x| x.name == last.src)
-                                        {
-                                            src_origin.get_offset()?.into()
-                                        } else {
-                                            log::warn!("Source origin \"{}\" not found, applying calibration with identity source", last.src);
-                                            TransformD::default()
-                                        };
-
-                                        for o in data.tracking_origins.iter() {
-                                            if o.name != last.dst {
-                                                continue;
-                                            }
-
-                                            let offset = last.offset * src_transform;
-                                            o.set_offset(offset.into())?;
-                                            log::info!(
-                                                "Offset successfully applied to: {}",
-                                                last.dst
-                                            );
-                                            break 'main_loop;
-                                        }
-                                        log::error!("No such tracking origin: {}", last.dst);
+                                }
+                                method.init(&mut data)?;
+                                calibrator = Some(Box::new(method));
+                            }
+                            Subcommands::Continue {
+                                ref profile,
+                                auto,
+                                fuzzy,
+                            } => {
+                                let profile = (!auto).then_some(profile.as_str());
+                                match continue_from_profile(&mut data, &config, profile, fuzzy) {
+                                    Ok(Some(mut c)) => {
+                                        c.init(&mut data)?;
+                                        calibrator = Some(c);
+                                        notify_ready("Following the profile in continuous mode.");
                                     }
-                                    OffsetType::Device => {
-                                        let Some(src_idx) =
-                                            data.devices.iter().position(|d| d.serial == last.src)
-                                        else {
-                                            log::error!("No such device: {}", last.src);
-                                            break 'main_loop;
-                                        };
-
-                                        let Some(dst_idx) =
-                                            data.devices.iter().position(|d| d.serial == last.dst)
-                                        else {
-                                            log::error!("No such device: {}", last.dst);
-                                            break 'main_loop;
-                                        };
-
-                                        log::info!(
-                                            "Starting continous mode from previous calibration."
-                                        );
-
-                                        calibrator = Some(Box::new({
-                                            let mut c = OffsetMethod::new_internal(
-                                                src_idx,
-                                                dst_idx,
-                                                last.offset,
-                                                0.02,
-                                            );
-                                            c.init(&mut data)?;
-                                            c
-                                        }));
+                                    Ok(None) => {
+                                        notify_ready("Applied tracking origin offsets.");
+                                        break 'main_loop;
                                     }
+                                    Err(e) => return Err(e),
                                 }
                             }
+                            Subcommands::Level {
+                                ref dev,
+                                captures,
+                                ref profile,
+                            } => {
+                                let Some(device) = data.find_device(dev) else {
+                                    log::error!("dev: no such device: {}", &dev);
+                                    break 'main_loop;
+                                };
+
+                                spawn_trigger_reader();
+                                calibrator = Some(Box::new({
+                                    let mut c = LevelMethod::new(
+                                        device,
+                                        captures.unwrap_or(4),
+                                        profile.clone(),
+                                    );
+                                    c.init(&mut data)?;
+                                    c
+                                }));
+                            }
                             Subcommands::Floor => {
                                 calibrator = Some(Box::new({
                                     let mut c = FloorMethod::new(&session)?;
@@ -618,12 +1601,12 @@ fn xr_loop(
                         log::info!("XrSession stopped.")
                     }
                     xr::SessionState::EXITING | xr::SessionState::LOSS_PENDING => {
-                        anyhow::bail!("XR session exiting");
+                        return Err(SessionLost("XR session exiting").into());
                     }
                     _ => {}
                 },
                 InstanceLossPending(_) => {
-                    anyhow::bail!("XR instance loss pending");
+                    return Err(SessionLost("XR instance loss pending").into());
                 }
                 EventsLost(e) => {
                     log::warn!("lost {} events", e.lost_event_count());
@@ -653,10 +1636,50 @@ fn xr_loop(
                 break 'main_loop;
             }
 
+            if last_refresh.elapsed() >= DEVICE_REFRESH_INTERVAL {
+                last_refresh = Instant::now();
+                let changes = data.refresh()?;
+                if !changes.is_empty() {
+                    let line = describe_changes(data, &changes);
+                    if let Some(cal) = calibrator.as_mut() {
+                        cal.devices_changed(data, &changes);
+                    }
+                    if let Some(ui) = tui.as_mut() {
+                        ui.set_status(line);
+                    }
+                    if let Some(watch) = watch.as_mut() {
+                        watch.devices_changed();
+                    }
+                }
+            }
+
             let calibrator_result = if let Some(cal) = calibrator.as_mut() {
-                let (result, new_status) = cal.step(data)?;
-                calibrator_status = new_status;
-                Some(result)
+                if TRIGGER.swap(false, Ordering::Relaxed) {
+                    cal.trigger();
+                }
+                match cal.step(data) {
+                    Ok((result, new_status)) => {
+                        calibrator_status = new_status;
+                        Some(result)
+                    }
+                    // the daemon and watch outlive a failed calibrator
+                    Err(e) if daemon.is_some() || watch.is_some() => {
+                        log::error!("Calibrator failed: {}", e);
+                        if let Err(e) = cal.finish(data) {
+                            log::warn!("Could not finish the calibrator: {}", e);
+                        }
+                        calibrator = None;
+                        calibrator_status = None;
+                        if let Some(daemon) = daemon.as_mut() {
+                            daemon.calibrator_failed();
+                        }
+                        if let Some(watch) = watch.as_mut() {
+                            watch.calibrator_failed();
+                        }
+                        None
+                    }
+                    Err(e) => return Err(e.into()),
+                }
             } else {
                 calibrator_status = None;
                 None
@@ -666,6 +1689,7 @@ fn xr_loop(
                 update_status(&status, &mut cli_status, calibrator_status.as_ref());
             }
 
+            // before the daemon and watch, which may swap the calibrator this result is about
             if let Some(result) = calibrator_result {
                 match result {
                     StepResult::End => {
@@ -679,6 +1703,8 @@ fn xr_loop(
 
                         if let Some(ui) = tui.as_mut() {
                             ui.set_status("Calibrator finished.");
+                        } else if daemon.is_some() || watch.is_some() {
+                            log::info!("Calibrator finished.");
                         } else {
                             log::info!("Our work here is done! ✅");
                             break 'main_loop;
@@ -691,13 +1717,68 @@ fn xr_loop(
                         if let Some(cal) = calibrator.as_mut() {
                             cal.finish(data)?;
                         }
-                        new_calibrator.init(data)?;
-                        calibrator = Some(new_calibrator);
+                        calibrator = None;
+                        match new_calibrator.init(data) {
+                            Ok(_) => calibrator = Some(new_calibrator),
+                            Err(e) if daemon.is_some() || watch.is_some() => {
+                                log::error!("Could not start the next calibrator: {}", e);
+                                if let Some(daemon) = daemon.as_mut() {
+                                    daemon.calibrator_failed();
+                                }
+                                if let Some(watch) = watch.as_mut() {
+                                    watch.calibrator_failed();
+                                }
+                            }
+                            Err(e) => return Err(e.into()),
+                        }
+
+                        // a calibration or capture went on to continuous mode, with a saved profile.
+                        // discover hands over twice, first to the calibration
+                        if let (
+                            true,
+                            Subcommands::Calibrate {
+                                r#continue: true,
+                                ref profile,
+                                ..
+                            }
+                            | Subcommands::Discover {
+                                calibrate: true,
+                                r#continue: true,
+                                ref profile,
+                                ..
+                            }
+                            | Subcommands::Offset { ref profile, .. },
+                        ) = (data.telemetry.continuous, command)
+                        {
+                            state.resume = Resume::of(&Subcommands::Continue {
+                                profile: profile.clone(),
+                                auto: false,
+                                fuzzy: false,
+                            });
+                        }
+                        if let Some(daemon) = daemon.as_mut() {
+                            daemon.calibrator_replaced();
+                        }
                     }
                     StepResult::Continue => {}
                 }
             }
 
+            if let Some(watch) = watch.as_mut() {
+                if let Some(name) = watch.step(data, &mut calibrator)? {
+                    notify_status(&format!("Applied profile '{}'.", name));
+                }
+            }
+
+            if let Some(daemon) = daemon.as_mut() {
+                daemon.step(&session, data, &mut calibrator, calibrator_status.as_ref())?;
+                if let Some(resume) = state.resume.as_mut() {
+                    resume.request = daemon.resume_request();
+                }
+            }
+
+            reporting.step(data, calibrator.is_some(), calibrator_status.as_ref());
+
             let tui_result = if let Some(ui) = tui.as_mut() {
                 Some(ui.step(data, calibrator_status.as_ref())?)
             } else {
@@ -720,8 +1801,16 @@ fn xr_loop(
                         if let Some(cal) = calibrator.as_mut() {
                             cal.finish(data)?;
                         }
-                        new_calibrator.init(data)?;
-                        calibrator = Some(new_calibrator);
+                        calibrator = None;
+                        // e.g. another motoc process owns the offset; keep the TUI running
+                        match new_calibrator.init(data) {
+                            Ok(_) => calibrator = Some(new_calibrator),
+                            Err(e) => {
+                                if let Some(ui) = tui.as_mut() {
+                                    ui.set_status(format!("Could not start: {}", e));
+                                }
+                            }
+                        }
                     }
                     StepResult::Continue => {}
                 }
@@ -734,56 +1823,98 @@ fn xr_loop(
     Ok(())
 }
 
-fn load_calibrator_data<'a, G>(
-    session: &xr::Session<G>,
-    monado: &'a mnd::Monado,
-) -> anyhow::Result<CalibratorData<'a>> {
-    let mut devices = vec![];
-    let mut tracking_origins = vec![];
-
-    let mut serial_space = HashMap::new();
+/// Applies the tracking origin offsets of a profile, or of the best fitting one if no
+/// name is given. Returns a calibrator that maintains its device offset, if it has one.
+fn continue_from_profile(
+    data: &mut CalibratorData,
+    config: &libmotoc::Config,
+    profile: Option<&str>,
+    fuzzy: bool,
+) -> anyhow::Result<Option<Box<dyn Calibrator>>> {
+    let last = if let Some(profile) = profile {
+        let Ok(last) = data.load_calibration(profile) else {
+            anyhow::bail!(
+                "Could not load calibration for profile '{}'. Did you mean to calibrate first?",
+                profile
+            );
+        };
+        last
+    } else {
+        let Some(choice) = data.select_profile()? else {
+            anyhow::bail!(
+                "No saved profile fits the connected devices. Did you mean to calibrate first?"
+            );
+        };
+        log::info!(
+            "Using profile '{}', the best fit of {}: {}",
+            choice.name,
+            choice.considered,
+            choice.fit.reasons.join(", ")
+        );
+        data.tuning = config.tuning(Some(&choice.name))?;
+        choice.profile
+    };
 
-    let xdev_list = session.get_xdev_list()?;
-    for xdev in xdev_list.enumerate_xdevs()?.into_iter() {
-        if !xdev.can_create_space() {
-            continue;
+    let matcher = libmotoc::NameMatcher::load(fuzzy);
+    let mut devices = Vec::new();
+    for saved in last.offsets.iter() {
+        match saved.offset_type {
+            OffsetType::TrackingOrigin => match data.apply_origin_offset(saved, &matcher) {
+                Ok(_) => log::info!("Offset successfully applied to: {}", saved.dst),
+                Err(e) => log::error!("{}", e),
+            },
+            OffsetType::Device => devices.push(saved),
         }
-        serial_space.insert(
-            xdev.serial().to_string(),
-            xdev.create_space(xr::Posef::IDENTITY)?,
+    }
+
+    let Some(saved) = devices.first() else {
+        return Ok(None);
+    };
+    if devices.len() > 1 {
+        log::warn!(
+            "Profile has {} device offsets, only following the first one.",
+            devices.len()
         );
     }
 
-    for dev in monado.devices()?.into_iter() {
-        let serial = dev.serial()?;
-        let Some(space) = serial_space.remove(serial.as_str()) else {
-            continue;
-        };
+    let Some(src_idx) = data.find_saved_device(&saved.src, &matcher) else {
+        anyhow::bail!("No such device: {}", saved.src);
+    };
+    let Some(dst_idx) = data.find_saved_device(&saved.dst, &matcher) else {
+        anyhow::bail!("No such device: {}", saved.dst);
+    };
 
-        devices.push(Device {
-            tracking_origin: dev.get_info_u32(mnd::MndProperty::PropertyTrackingOriginU32)?,
-            serial,
-            space,
-            index: dev.index,
-            inner: dev,
-        });
-    }
+    log::info!("Starting continous mode from previous calibration.");
+    Ok(Some(Box::new(OffsetMethod::new_internal(
+        src_idx,
+        dst_idx,
+        saved.offset,
+        data.tuning.lerp_factor,
+    ))))
+}
 
-    for to in monado.tracking_origins()?.into_iter() {
-        tracking_origins.push(to);
-    }
-
-    Ok(CalibratorData {
-        monado,
-        devices,
-        tracking_origins,
-        stage: session
-            .create_reference_space(xr::ReferenceSpaceType::STAGE, xr::Posef::IDENTITY)?,
-        local: session
-            .create_reference_space(xr::ReferenceSpaceType::LOCAL, xr::Posef::IDENTITY)?,
-        view: session.create_reference_space(xr::ReferenceSpaceType::VIEW, xr::Posef::IDENTITY)?,
-        now: session.instance().now()?,
-    })
+/// Logs devices that came or went, and returns the same as one line.
+fn describe_changes(data: &CalibratorData, changes: &DeviceChanges) -> String {
+    let serials = |indices: &[usize]| {
+        indices
+            .iter()
+            .map(|&i| data.devices[i].serial.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut parts = Vec::new();
+    if !changes.added.is_empty() {
+        parts.push(format!("Connected: {}.", serials(&changes.added)));
+    }
+    if !changes.removed.is_empty() {
+        parts.push(format!("Disconnected: {}.", serials(&changes.removed)));
+    }
+    if changes.origins_changed {
+        parts.push(String::from("Tracking origins changed."));
+    }
+    let line = parts.join(" ");
+    log::info!("{}", line);
+    line
 }
 
 #[derive(clap::Parser, Debug)]
@@ -796,9 +1927,38 @@ struct Args {
     /// Wait for Monado to become available (instead of exiting)
     #[arg(short, long)]
     wait: bool,
+
+    /// Send the command to a running `motoc daemon` instead of running it here.
+    /// Works with calibrate, continue, adjust, recenter, floor and reset
+    #[arg(long, global = true)]
+    via_daemon: bool,
+
+    /// Serve OpenMetrics on this address while running, for Prometheus and the like.
+    /// Without an address, 127.0.0.1:9464 is used. Pass one as --metrics=ADDRESS:PORT
+    #[arg(
+        long,
+        global = true,
+        value_name = "ADDRESS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = libmotoc::DEFAULT_METRICS_ADDR
+    )]
+    metrics: Option<SocketAddr>,
+
+    /// Send the calibration state as OSC messages to HOST:PORT while running.
+    /// Without one, 127.0.0.1:9000 is used. Pass one as --osc=HOST:PORT
+    #[arg(
+        long,
+        global = true,
+        value_name = "HOST:PORT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = libmotoc::DEFAULT_OSC_TARGET
+    )]
+    osc: Option<String>,
 }
 
-#[derive(clap::Parser, Debug)]
+#[derive(clap::Parser, Clone, Debug)]
 enum Subcommands {
     /// Show available tracking origings and their devices
     Show,
@@ -840,13 +2000,44 @@ enum Subcommands {
         #[arg(long)]
         z: Option<f64>,
 
-        /// interpolation factor, lower is smoother. range (0, 1]
-        #[arg(long, value_name = "FACTOR", default_value = "0.05")]
-        lerp: f64,
+        /// interpolation factor, lower is smoother. range (0, 1]. default: 0.05, see config.toml
+        #[arg(long, value_name = "FACTOR")]
+        lerp: Option<f64>,
+
+        /// measure the current offset between the devices once Enter is pressed, instead of
+        /// passing it by hand. the measured offset is saved to the profile
+        #[arg(long, conflicts_with_all = ["yaw", "pitch", "roll", "x", "y", "z"])]
+        capture: bool,
+
+        /// start from a named mount preset instead of passing the offset by hand.
+        /// see `motoc presets` for the available ones
+        #[arg(
+            long,
+            value_name = "NAME",
+            conflicts_with_all = ["yaw", "pitch", "roll", "x", "y", "z", "capture"]
+        )]
+        preset: Option<String>,
+
+        /// refine the preset with a short sampled calibration. move the devices around
+        /// as with `motoc calibrate`
+        #[arg(long, requires = "preset")]
+        refine: bool,
+
+        /// save the captured or preset offset with this profile name
+        #[arg(long, value_name = "NAME", default_value = "last")]
+        profile: String,
+    },
+    /// List the mount presets available to `motoc offset --preset`
+    Presets,
+    /// Manage saved calibration profiles
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
     },
     /// Calibrate by sampling two devices that move together over time
     Calibrate {
-        /// the numeric id or serial number of the source device (usu. HMD)
+        /// the numeric id or serial number of the source device (usu. HMD),
+        /// or a hand tracking joint such as hand:left:wrist
         #[arg(long, value_name = "DEVICE")]
         src: String,
 
@@ -858,16 +2049,74 @@ enum Subcommands {
         #[arg(long)]
         r#continue: bool,
 
-        /// number of samples to use for initial calibration. default: 500
+        /// number of samples to use for initial calibration. default: 500, see config.toml
         #[arg(long)]
         samples: Option<u32>,
 
+        /// start sampling right away instead of waiting for the devices to move together
+        #[arg(long)]
+        no_wait: bool,
+
         /// save the calubration with this profile name
         #[arg(long, value_name = "NAME", default_value = "last")]
         profile: String,
     },
+    /// Find the device pair that is physically attached by moving it around
+    Discover {
+        /// how long to watch the devices for, in seconds
+        #[arg(long, default_value = "5")]
+        seconds: f64,
+
+        /// calibrate the detected pair right away
+        #[arg(long)]
+        calibrate: bool,
+
+        /// continue maintaining offset after calibration. enable if the devices are firmly attached
+        #[arg(long, requires = "calibrate")]
+        r#continue: bool,
+
+        /// number of samples to use for calibration. default: 500, see config.toml
+        #[arg(long, requires = "calibrate")]
+        samples: Option<u32>,
+
+        /// save the calibration with this profile name
+        #[arg(long, value_name = "NAME", default_value = "last")]
+        profile: String,
+    },
+    /// Calibrate by touching the same physical spots with a device from each origin
+    Points {
+        /// the numeric id or serial number of the source device (usu. a controller)
+        #[arg(long, value_name = "DEVICE")]
+        src: String,
+
+        /// the numeric id or serial number of the destination device (usu. tracker)
+        #[arg(long, value_name = "DEVICE")]
+        dst: String,
+
+        /// number of spots to touch, at least 3. press Enter after each touch. default: 4
+        #[arg(long)]
+        points: Option<u32>,
+
+        /// save the calibration with this profile name
+        #[arg(long, value_name = "NAME", default_value = "last")]
+        profile: String,
+    },
     /// Auto-adjust the floor level using hand tracking, by placing hands on floor
     Floor,
+    /// Level a tilted tracking origin by turning one of its devices on the floor
+    Level {
+        /// the numeric id or serial number of a device in the tilted origin (usu. tracker)
+        #[arg(long, value_name = "DEVICE")]
+        dev: String,
+
+        /// number of placements on the floor. default: 4
+        #[arg(long)]
+        captures: Option<u32>,
+
+        /// also save the leveled origin with this profile name
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+    },
     /// Manually adjust the offset of the given tracking origin
     Adjust {
         /// tracking origin ID from `motoc show`
@@ -914,9 +2163,258 @@ enum Subcommands {
         /// load the calubration from this profile
         #[arg(long, value_name = "NAME", default_value = "last")]
         profile: String,
+
+        /// pick the profile that best fits the connected devices and tracking origins
+        #[arg(long, conflicts_with = "profile")]
+        auto: bool,
+
+        /// use similarly named origins and devices when the saved ones are missing
+        #[arg(long)]
+        fuzzy: bool,
+    },
+    /// Keep running and apply a profile whenever its devices appear and are tracking
+    Watch {
+        /// the profile to apply. default: the one that best fits the connected devices
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// use similarly named origins and devices when the saved ones are missing
+        #[arg(long)]
+        fuzzy: bool,
+    },
+    /// Import a calibration saved by OpenVR-SpaceCalibrator
+    ImportSvc {
+        /// the OpenVR-SpaceCalibrator profile file
+        #[arg(value_name = "FILE")]
+        file: String,
+
+        /// save the calibration with this profile name
+        #[arg(long, value_name = "NAME", default_value = "last")]
+        profile: String,
+
+        /// Monado tracking origin to use as reference. default: the reference tracking system
+        #[arg(long, value_name = "ORIGIN")]
+        src: Option<String>,
+
+        /// Monado tracking origin to calibrate. default: the target tracking system
+        #[arg(long, value_name = "ORIGIN")]
+        dst: Option<String>,
+    },
+    /// Export a tracking origin offset for OpenVR-SpaceCalibrator
+    ExportSvc {
+        #[arg(value_name = "PROFILE")]
+        profile: String,
+
+        /// which tracking origin offset to export, if the profile has several
+        #[arg(long, value_name = "ORIGIN")]
+        dst: Option<String>,
+
+        /// write to this file instead of printing
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Make the HMD follow a tracker via Monado's config, based on the device offsets of a profile.
+    /// Not the same as continuous mode: the HMD's own tracking is replaced
+    ExportMonado {
+        #[arg(value_name = "PROFILE")]
+        profile: String,
+
+        /// write the merged config back instead of printing it
+        #[arg(long)]
+        write: bool,
+
+        /// Monado config file to merge into. default: ~/.config/monado/config_v0.json
+        #[arg(long, value_name = "FILE")]
+        config: Option<String>,
+    },
+    /// Print the tuning values in effect, after applying config.toml
+    Config {
+        /// include the overrides for this profile
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+    },
+    /// Keep the XR session open and take commands over a socket in $XDG_RUNTIME_DIR.
+    /// Without a subcommand, runs the daemon
+    Daemon {
+        #[command(subcommand)]
+        command: Option<DaemonCommand>,
+        /// also serve io.github.galister.Motoc on the D-Bus session bus
+        #[arg(long)]
+        dbus: bool,
+    },
+    /// Run motoc at login as a systemd user service
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommand,
+    },
+    /// Manage alternative names for tracking origins and device serials
+    Alias {
+        #[command(subcommand)]
+        command: AliasCommand,
+    },
+    /// Save, restore or compare the STAGE, LOCAL and tracking origin offsets all at once
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommand,
+    },
+    /// Revert the last changes motoc made to any offset
+    Undo {
+        /// number of changes to revert
+        #[arg(value_name = "N", default_value = "1")]
+        count: usize,
+    },
+    /// List recent changes motoc made to offsets
+    History {
+        /// number of changes to show
+        #[arg(long, short = 'n', default_value = "20")]
+        count: usize,
     },
     /// Check if Monado is reachable, then exit.
     Check,
     /// Return the number of discovered devices
     NumDevices,
 }
+
+impl Subcommands {
+    /// The profile a command saves to or loads from, whose config.toml overrides apply.
+    fn profile(&self) -> Option<&str> {
+        match self {
+            Subcommands::Offset { profile, .. }
+            | Subcommands::Calibrate { profile, .. }
+            | Subcommands::Discover { profile, .. }
+            | Subcommands::Points { profile, .. } => Some(profile),
+            Subcommands::Continue {
+                profile,
+                auto: false,
+                ..
+            } => Some(profile),
+            Subcommands::Watch { profile, .. } => profile.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+enum ProfileCommand {
+    /// List saved profiles with what they are for and when they were made
+    List,
+    /// Show all details of a profile
+    Show {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+    /// Delete a profile
+    Delete {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+    /// Rename a profile
+    Rename {
+        #[arg(value_name = "FROM")]
+        from: String,
+        #[arg(value_name = "TO")]
+        to: String,
+    },
+    /// Copy a profile under a new name
+    Copy {
+        #[arg(value_name = "FROM")]
+        from: String,
+        #[arg(value_name = "TO")]
+        to: String,
+    },
+    /// Replace a tracking origin name or device serial that has changed
+    Rebind {
+        #[arg(value_name = "NAME")]
+        name: String,
+        /// the name or serial as saved in the profile
+        #[arg(long, value_name = "OLD")]
+        from: String,
+        /// the name or serial it has now
+        #[arg(long, value_name = "NEW")]
+        to: String,
+    },
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+enum ServiceCommand {
+    /// Write ~/.config/systemd/user/motoc.service, then enable and start it.
+    /// It runs `motoc --wait continue --profile last` unless told otherwise
+    Install {
+        /// the profile to continue from, or to watch for
+        #[arg(long, value_name = "NAME", conflicts_with = "daemon")]
+        profile: Option<String>,
+        /// run `motoc watch`, which waits for the devices to show up
+        #[arg(long, conflicts_with = "daemon")]
+        watch: bool,
+        /// run `motoc daemon`
+        #[arg(long)]
+        daemon: bool,
+        /// replace an existing service
+        #[arg(long)]
+        force: bool,
+        /// only write the unit file
+        #[arg(long)]
+        no_enable: bool,
+    },
+    /// Stop, disable and remove the service
+    Uninstall,
+    /// Show whether the service is installed and running
+    Status,
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+enum DaemonCommand {
+    /// Show what the running daemon is doing
+    Status,
+    /// Stop the daemon's running calibration
+    Stop,
+    /// Stop the daemon
+    Shutdown,
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+enum AliasCommand {
+    /// List all aliases
+    List,
+    /// Treat ALIAS as another name of NAME when loading profiles
+    Add {
+        #[arg(value_name = "NAME")]
+        name: String,
+        #[arg(value_name = "ALIAS")]
+        alias: String,
+        /// NAME is a device serial rather than a tracking origin
+        #[arg(long)]
+        device: bool,
+    },
+    /// Remove an alias
+    Remove {
+        #[arg(value_name = "ALIAS")]
+        alias: String,
+        /// the alias is for a device serial rather than a tracking origin
+        #[arg(long)]
+        device: bool,
+    },
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+enum SnapshotCommand {
+    /// Save all current offsets
+    Save {
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// replace an existing snapshot with the same name
+        #[arg(long)]
+        force: bool,
+    },
+    /// Put back all offsets from a snapshot. tracking origins are matched by name
+    Restore {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+    /// Show how far each offset has moved since a snapshot
+    Diff {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+}
@@ -18,7 +18,7 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use libmonado::{self as mnd, DeviceLogic};
-use libmotoc::{CalibratorData, ResultExt};
+use libmotoc::{CalibratorData, MountPreset, NameMatcher, PairDiscovery, ResultExt, SaveInfo};
 use log::Level;
 use nalgebra::{Quaternion, Rotation3, UnitQuaternion, Vector3};
 use openxr::SpaceVelocityFlags;
@@ -33,14 +33,24 @@ use ratatui::{
 
 use crate::{OffsetType, TransformD};
 
-use super::{CalibratorStatus, OffsetMethod, RecenterMethod, SampledMethod, StepResult};
+use super::{
+    CalibratorStatus, OffsetMethod, RecenterMethod, SampledMethod, StepResult, REFINE_SAMPLES,
+};
 
 pub type Result<T> = std::result::Result<T, libmotoc::Error>;
 
 const TICKER_SIZE: usize = 10;
 const MAX_LOG_LINES: usize = 200;
 pub const SPINNER_TICK_CHARS: &str = "⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏";
-const COMMANDS: [&str; 5] = ["Continue", "Calibrate", "Adjust", "Recenter", "Reset"];
+const COMMANDS: [&str; 6] = [
+    "Continue",
+    "Calibrate",
+    "Adjust",
+    "Recenter",
+    "Reset",
+    "Presets",
+];
+const DETECT_SECONDS: f64 = 5.0;
 const OFFSET_DELTAS: [f64; 8] = [-10.0, -1.0, -0.1, -0.01, 0.01, 0.1, 1.0, 10.0];
 
 type TuiTerminal = Terminal<CrosstermBackend<Stdout>>;
@@ -209,10 +219,24 @@ struct CalibrateForm {
     editing_samples: bool,
 }
 
+#[derive(Clone, Debug)]
+struct PresetForm {
+    presets: Vec<MountPreset>,
+    preset: usize,
+    source: usize,
+    target: usize,
+    // the profile the offset is saved to, `last` first
+    profiles: Vec<String>,
+    profile: usize,
+    refine: bool,
+    selected: usize,
+}
+
 #[derive(Clone, Debug)]
 enum Screen {
     Dashboard,
     Calibrate(CalibrateForm),
+    Preset(PresetForm),
     AdjustSelect {
         selected: usize,
     },
@@ -236,6 +260,12 @@ enum MouseAction {
     CalibrateSamples(i32),
     CalibrateToggle,
     CalibrateStart,
+    CalibrateDetect,
+    PresetCycle(isize),
+    PresetCycleProfile(isize),
+    PresetCycleDevice { source: bool, delta: isize },
+    PresetToggle,
+    PresetStart,
     AdjustTarget(usize),
     AdjustDelta { axis: Axis, delta: f64 },
     Recenter(SpaceKind),
@@ -268,6 +298,7 @@ pub struct Tui {
     hitboxes: Vec<Hitbox>,
     overview_area: Rect,
     status: String,
+    discovery: Option<PairDiscovery>,
 }
 
 impl Tui {
@@ -283,6 +314,7 @@ impl Tui {
             hitboxes: Vec::new(),
             overview_area: Rect::default(),
             status: String::new(),
+            discovery: None,
         }
     }
 
@@ -449,6 +481,10 @@ impl Tui {
                     self.overview_scroll = self.overview_scroll.saturating_add(5);
                     StepResult::Continue
                 }
+                KeyCode::Char('u') => {
+                    self.undo_last(data);
+                    StepResult::Continue
+                }
                 _ => StepResult::Continue,
             },
             Screen::Calibrate(mut form) => match key.code {
@@ -463,7 +499,7 @@ impl Tui {
                     StepResult::Continue
                 }
                 KeyCode::Down | KeyCode::Char('j') | KeyCode::Tab => {
-                    form.selected = (form.selected + 1).min(5);
+                    form.selected = (form.selected + 1).min(6);
                     form.editing_samples = false;
                     self.screen = Screen::Calibrate(form);
                     StepResult::Continue
@@ -523,6 +559,11 @@ impl Tui {
                         StepResult::Continue
                     }
                     4 => self.start_calibration(&form, data),
+                    5 => {
+                        self.start_detection(data);
+                        self.screen = Screen::Calibrate(form);
+                        StepResult::Continue
+                    }
                     _ => {
                         self.screen = Screen::Dashboard;
                         StepResult::Continue
@@ -530,6 +571,42 @@ impl Tui {
                 },
                 _ => StepResult::Continue,
             },
+            Screen::Preset(mut form) => match key.code {
+                KeyCode::Esc => {
+                    self.screen = Screen::Dashboard;
+                    StepResult::Continue
+                }
+                KeyCode::Up | KeyCode::Char('k') | KeyCode::BackTab => {
+                    form.selected = form.selected.saturating_sub(1);
+                    self.screen = Screen::Preset(form);
+                    StepResult::Continue
+                }
+                KeyCode::Down | KeyCode::Char('j') | KeyCode::Tab => {
+                    form.selected = (form.selected + 1).min(5);
+                    self.screen = Screen::Preset(form);
+                    StepResult::Continue
+                }
+                KeyCode::Left => {
+                    adjust_preset_field(&mut form, data, -1);
+                    self.screen = Screen::Preset(form);
+                    StepResult::Continue
+                }
+                KeyCode::Right => {
+                    adjust_preset_field(&mut form, data, 1);
+                    self.screen = Screen::Preset(form);
+                    StepResult::Continue
+                }
+                KeyCode::Enter | KeyCode::Char(' ') => {
+                    if form.selected == 5 {
+                        self.start_preset(&form, data)
+                    } else {
+                        adjust_preset_field(&mut form, data, 1);
+                        self.screen = Screen::Preset(form);
+                        StepResult::Continue
+                    }
+                }
+                _ => StepResult::Continue,
+            },
             Screen::AdjustSelect { mut selected } => {
                 let targets = offset_targets(data);
                 match key.code {
@@ -593,6 +670,7 @@ impl Tui {
                     KeyCode::Enter | KeyCode::Char(' ') => {
                         self.apply_adjust(&target, axis, OFFSET_DELTAS[selected_delta], data);
                     }
+                    KeyCode::Char('u') => self.undo_last(data),
                     KeyCode::Char(ch @ '1'..='4') => {
                         let magnitude = ch as usize - '1' as usize;
                         selected_delta = if OFFSET_DELTAS[selected_delta].is_sign_negative() {
@@ -707,7 +785,10 @@ impl Tui {
             }
             MouseAction::CalibrateSamples(delta) => {
                 if let Screen::Calibrate(mut form) = self.screen.clone() {
-                    let value = form.samples.parse::<i64>().unwrap_or(500);
+                    let value = form
+                        .samples
+                        .parse::<i64>()
+                        .unwrap_or(data.tuning.samples.into());
                     form.samples = (value + i64::from(delta)).max(1).to_string();
                     form.selected = 2;
                     form.editing_samples = false;
@@ -730,6 +811,58 @@ impl Tui {
                     StepResult::Continue
                 }
             }
+            MouseAction::CalibrateDetect => {
+                if let Screen::Calibrate(mut form) = self.screen.clone() {
+                    form.selected = 5;
+                    self.screen = Screen::Calibrate(form);
+                    self.start_detection(data);
+                }
+                StepResult::Continue
+            }
+            MouseAction::PresetCycle(delta) => {
+                if let Screen::Preset(mut form) = self.screen.clone() {
+                    cycle_index(&mut form.preset, form.presets.len(), delta);
+                    form.selected = 0;
+                    self.screen = Screen::Preset(form);
+                }
+                StepResult::Continue
+            }
+            MouseAction::PresetCycleDevice { source, delta } => {
+                if let Screen::Preset(mut form) = self.screen.clone() {
+                    let index = if source {
+                        &mut form.source
+                    } else {
+                        &mut form.target
+                    };
+                    cycle_index(index, data.devices.len(), delta);
+                    form.selected = if source { 1 } else { 2 };
+                    self.screen = Screen::Preset(form);
+                }
+                StepResult::Continue
+            }
+            MouseAction::PresetCycleProfile(delta) => {
+                if let Screen::Preset(mut form) = self.screen.clone() {
+                    cycle_index(&mut form.profile, form.profiles.len(), delta);
+                    form.selected = 3;
+                    self.screen = Screen::Preset(form);
+                }
+                StepResult::Continue
+            }
+            MouseAction::PresetToggle => {
+                if let Screen::Preset(mut form) = self.screen.clone() {
+                    form.refine = !form.refine;
+                    form.selected = 4;
+                    self.screen = Screen::Preset(form);
+                }
+                StepResult::Continue
+            }
+            MouseAction::PresetStart => {
+                if let Screen::Preset(form) = self.screen.clone() {
+                    self.start_preset(&form, data)
+                } else {
+                    StepResult::Continue
+                }
+            }
             MouseAction::AdjustTarget(index) => {
                 if let Some(target) = offset_targets(data).get(index).cloned() {
                     self.screen = Screen::AdjustEdit {
@@ -794,7 +927,7 @@ impl Tui {
                 self.screen = Screen::Calibrate(CalibrateForm {
                     source,
                     target,
-                    samples: "500".into(),
+                    samples: data.tuning.samples.to_string(),
                     continuous: false,
                     selected: 0,
                     editing_samples: false,
@@ -813,6 +946,41 @@ impl Tui {
                 self.screen = Screen::Reset { selected: 0 };
                 StepResult::Continue
             }
+            5 => {
+                if data.devices.len() < 2 {
+                    self.status = "Presets need at least two devices.".into();
+                    return StepResult::Continue;
+                }
+                let presets = match libmotoc::load_presets() {
+                    Ok(presets) => presets,
+                    Err(e) => {
+                        self.status = format!("Could not load presets: {e}");
+                        return StepResult::Continue;
+                    }
+                };
+                let mut profiles = vec![String::from("last")];
+                if let Ok(saved) = libmotoc::list_profiles() {
+                    profiles.extend(
+                        saved
+                            .into_iter()
+                            .map(|(name, _)| name)
+                            .filter(|name| name != "last"),
+                    );
+                }
+                let source = guess_source_device(data);
+                let target = guess_target_device(data, source);
+                self.screen = Screen::Preset(PresetForm {
+                    presets,
+                    preset: 0,
+                    source,
+                    target,
+                    profiles,
+                    profile: 0,
+                    refine: false,
+                    selected: 0,
+                });
+                StepResult::Continue
+            }
             _ => StepResult::Continue,
         }
     }
@@ -827,7 +995,10 @@ impl Tui {
             0 => cycle_index(&mut form.source, data.devices.len(), delta),
             1 => cycle_index(&mut form.target, data.devices.len(), delta),
             2 => {
-                let value = form.samples.parse::<i64>().unwrap_or(500);
+                let value = form
+                    .samples
+                    .parse::<i64>()
+                    .unwrap_or(data.tuning.samples.into());
                 form.samples = (value + (delta as i64 * 50)).max(1).to_string();
                 form.editing_samples = false;
             }
@@ -837,20 +1008,8 @@ impl Tui {
     }
 
     fn start_calibration(&mut self, form: &CalibrateForm, data: &CalibratorData<'_>) -> StepResult {
-        let Some(source) = data.devices.get(form.source) else {
-            self.status = "The source device is no longer available.".into();
-            return StepResult::Continue;
-        };
-        let Some(target) = data.devices.get(form.target) else {
-            self.status = "The target device is no longer available.".into();
-            return StepResult::Continue;
-        };
-        if form.source == form.target {
-            self.status = "Source and target must be different devices.".into();
-            return StepResult::Continue;
-        }
-        if source.tracking_origin == target.tracking_origin {
-            self.status = "Source and target must use different tracking origins.".into();
+        if let Err(message) = check_device_pair(data, form.source, form.target) {
+            self.status = message;
             return StepResult::Continue;
         }
         let Ok(samples) = form.samples.parse::<u32>() else {
@@ -873,6 +1032,94 @@ impl Tui {
         )))
     }
 
+    fn start_detection(&mut self, data: &CalibratorData<'_>) {
+        if self.discovery.is_some() {
+            return;
+        }
+        let discovery = PairDiscovery::new(data, DETECT_SECONDS);
+        if discovery.is_empty() {
+            self.status = "Detection needs devices from two tracking origins.".into();
+            return;
+        }
+        self.discovery = Some(discovery);
+        self.status = "Move the attached devices around...".into();
+    }
+
+    fn update_detection(&mut self, data: &CalibratorData<'_>) {
+        let Some(discovery) = self.discovery.as_mut() else {
+            return;
+        };
+        if !discovery.update(data) {
+            self.status = format!(
+                "Move the attached devices around... {:.0}%",
+                discovery.progress(data) * 100.0
+            );
+            return;
+        }
+
+        let best = discovery.best();
+        self.discovery = None;
+        let Some(best) = best else {
+            self.status = "Could not detect an attached pair, move the devices more.".into();
+            return;
+        };
+
+        if let Screen::Calibrate(form) = &mut self.screen {
+            form.source = best.src_dev;
+            form.target = best.dst_dev;
+        }
+        self.status = format!(
+            "Detected {} + {}.",
+            device_label(data, best.src_dev),
+            device_label(data, best.dst_dev)
+        );
+    }
+
+    fn start_preset(&mut self, form: &PresetForm, data: &CalibratorData<'_>) -> StepResult {
+        if let Err(message) = check_device_pair(data, form.source, form.target) {
+            self.status = message;
+            return StepResult::Continue;
+        }
+        let Some(preset) = form.presets.get(form.preset) else {
+            self.status = "No presets, check ~/.config/motoc/presets.json.".into();
+            return StepResult::Continue;
+        };
+        let profile = form.profiles[form.profile].as_str();
+
+        self.screen = Screen::Dashboard;
+        if form.refine {
+            self.status = format!("Refining preset {}.", preset.name);
+            return StepResult::Replace(Box::new(
+                SampledMethod::new(
+                    form.source,
+                    form.target,
+                    true,
+                    REFINE_SAMPLES,
+                    profile.into(),
+                )
+                .refine(preset.offset()),
+            ));
+        }
+
+        self.status = match data.save_calibration(
+            profile,
+            form.source,
+            form.target,
+            preset.offset(),
+            OffsetType::Device,
+            SaveInfo::default(),
+        ) {
+            Ok(_) => format!("Preset {} applied.", preset.name),
+            Err(e) => format!("Could not save calibration: {e}"),
+        };
+        StepResult::Replace(Box::new(OffsetMethod::new_internal(
+            form.source,
+            form.target,
+            preset.offset(),
+            data.tuning.offset_lerp_factor,
+        )))
+    }
+
     fn start_recenter(&mut self, space: SpaceKind) -> StepResult {
         let height = None;
         match RecenterMethod::new(space.argument(), &height) {
@@ -912,7 +1159,7 @@ impl Tui {
                     offset.basis = rotation * offset.basis;
                 }
             }
-            set_target_offset(target, offset, data)
+            set_target_offset(target, offset, data, "tui adjust")
         })();
 
         self.status = match result {
@@ -927,8 +1174,21 @@ impl Tui {
         };
     }
 
+    fn undo_last(&mut self, data: &CalibratorData<'_>) {
+        self.status = match libmotoc::undo(data.monado, 1) {
+            Ok(undone) => match undone.first() {
+                Some(entry) => format!(
+                    "Undid #{} ({}) on {}.",
+                    entry.id, entry.source, entry.target
+                ),
+                None => String::from("Nothing to undo."),
+            },
+            Err(error) => format!("Could not undo: {error}"),
+        };
+    }
+
     fn reset_target(&mut self, target: &AdjustTarget, data: &CalibratorData<'_>) {
-        self.status = match set_target_offset(target, TransformD::default(), data) {
+        self.status = match set_target_offset(target, TransformD::default(), data, "tui reset") {
             Ok(()) => format!("{} has been reset.", target.label()),
             Err(message) => message,
         };
@@ -957,6 +1217,7 @@ impl Tui {
         data: &mut CalibratorData<'_>,
         calibrator_status: Option<&CalibratorStatus>,
     ) -> Result<StepResult> {
+        self.update_detection(data);
         self.draw(data, calibrator_status)?;
 
         while event::poll(Duration::ZERO).context("Unable to poll terminal input")? {
@@ -994,61 +1255,54 @@ impl Tui {
 fn continue_last_calibration(
     data: &mut CalibratorData<'_>,
 ) -> std::result::Result<StepResult, String> {
-    let last = data.load_calibration("last").map_err(|error| {
-        format!(
-            "Could not load the 'last' calibration. Calibrate first, or check the saved profile: \
-             {error}"
-        )
-    })?;
-
-    match last.offset_type {
-        OffsetType::TrackingOrigin => {
-            let source = data
-                .tracking_origins
-                .iter()
-                .find(|origin| origin.name == last.src)
-                .map(|origin| {
-                    origin
-                        .get_offset()
-                        .map(|pose| -> TransformD { pose.into() })
-                        .map_err(|error| error.to_string())
-                })
-                .transpose()?
-                .unwrap_or_default();
-
-            let Some(target) = data
-                .tracking_origins
-                .iter()
-                .find(|origin| origin.name == last.dst)
-            else {
-                return Err(format!("No such tracking origin: {}", last.dst));
-            };
+    let choice = data
+        .select_profile()
+        .map_err(|error| format!("Could not read the saved profiles: {error}"))?
+        .ok_or_else(|| {
+            String::from("No saved profile fits the connected devices. Calibrate first.")
+        })?;
+    log::info!(
+        "Using profile '{}', the best fit of {}: {}",
+        choice.name,
+        choice.considered,
+        choice.fit.reasons.join(", ")
+    );
+    data.tuning = libmotoc::Config::load()
+        .and_then(|config| config.tuning(Some(&choice.name)))
+        .map_err(|error| format!("Could not read config.toml: {error}"))?;
+    let last = choice.profile;
+    let matcher = NameMatcher::load(false);
+
+    for saved in last
+        .offsets
+        .iter()
+        .filter(|saved| saved.offset_type == OffsetType::TrackingOrigin)
+    {
+        data.apply_origin_offset(saved, &matcher)
+            .map_err(|error| format!("Could not apply calibration: {error}"))?;
+    }
 
-            target
-                .set_offset((last.offset * source).into())
-                .map_err(|error| format!("Could not apply calibration: {error}"))?;
-            Ok(StepResult::Continue)
-        }
-        OffsetType::Device => {
-            let source = data
-                .devices
-                .iter()
-                .position(|device| device.serial == last.src)
-                .ok_or_else(|| format!("No such device: {}", last.src))?;
-            let target = data
-                .devices
-                .iter()
-                .position(|device| device.serial == last.dst)
-                .ok_or_else(|| format!("No such device: {}", last.dst))?;
+    let Some(saved) = last
+        .offsets
+        .iter()
+        .find(|saved| saved.offset_type == OffsetType::Device)
+    else {
+        return Ok(StepResult::Continue);
+    };
 
-            Ok(StepResult::Replace(Box::new(OffsetMethod::new_internal(
-                source,
-                target,
-                last.offset,
-                0.02,
-            ))))
-        }
-    }
+    let source = data
+        .find_saved_device(&saved.src, &matcher)
+        .ok_or_else(|| format!("No such device: {}", saved.src))?;
+    let target = data
+        .find_saved_device(&saved.dst, &matcher)
+        .ok_or_else(|| format!("No such device: {}", saved.dst))?;
+
+    Ok(StepResult::Replace(Box::new(OffsetMethod::new_internal(
+        source,
+        target,
+        saved.offset,
+        data.tuning.lerp_factor,
+    ))))
 }
 
 fn get_target_offset(
@@ -1076,19 +1330,22 @@ fn set_target_offset(
     target: &AdjustTarget,
     offset: TransformD,
     data: &CalibratorData<'_>,
+    source: &str,
 ) -> std::result::Result<(), String> {
     match target {
-        AdjustTarget::Space(space) => data
-            .monado
-            .set_reference_space_offset(space.reference_type(), offset.into())
-            .map_err(|error| format!("Could not update the {} offset: {error}", target.label())),
-        AdjustTarget::TrackingOrigin { id, .. } => data
-            .tracking_origins
-            .iter()
-            .find(|origin| origin.id == *id)
-            .ok_or_else(|| format!("{} is no longer available.", target.label()))?
-            .set_offset(offset.into())
-            .map_err(|error| format!("Could not update the {} offset: {error}", target.label())),
+        AdjustTarget::Space(space) => {
+            libmotoc::set_space_offset(data.monado, space.reference_type(), offset, source)
+                .map_err(|error| format!("Could not update the {} offset: {error}", target.label()))
+        }
+        AdjustTarget::TrackingOrigin { id, .. } => libmotoc::set_origin_offset(
+            data.tracking_origins
+                .iter()
+                .find(|origin| origin.id == *id)
+                .ok_or_else(|| format!("{} is no longer available.", target.label()))?,
+            offset,
+            source,
+        )
+        .map_err(|error| format!("Could not update the {} offset: {error}", target.label())),
     }
 }
 
@@ -1110,6 +1367,43 @@ fn guess_target_device(data: &CalibratorData<'_>, source: usize) -> usize {
         .unwrap_or(if source == 0 { 1 } else { 0 })
 }
 
+fn check_device_pair(
+    data: &CalibratorData<'_>,
+    source: usize,
+    target: usize,
+) -> std::result::Result<(), String> {
+    let Some(source_device) = data.devices.get(source) else {
+        return Err("The source device is no longer available.".into());
+    };
+    let Some(target_device) = data.devices.get(target) else {
+        return Err("The target device is no longer available.".into());
+    };
+    if !source_device.connected {
+        return Err("The source device is disconnected.".into());
+    }
+    if !target_device.connected {
+        return Err("The target device is disconnected.".into());
+    }
+    if source == target {
+        return Err("Source and target must be different devices.".into());
+    }
+    if source_device.tracking_origin == target_device.tracking_origin {
+        return Err("Source and target must use different tracking origins.".into());
+    }
+    Ok(())
+}
+
+fn adjust_preset_field(form: &mut PresetForm, data: &CalibratorData<'_>, delta: isize) {
+    match form.selected {
+        0 => cycle_index(&mut form.preset, form.presets.len(), delta),
+        1 => cycle_index(&mut form.source, data.devices.len(), delta),
+        2 => cycle_index(&mut form.target, data.devices.len(), delta),
+        3 => cycle_index(&mut form.profile, form.profiles.len(), delta),
+        4 => form.refine = !form.refine,
+        _ => {}
+    }
+}
+
 fn cycle_index(index: &mut usize, len: usize, delta: isize) {
     if len == 0 {
         *index = 0;
@@ -1172,6 +1466,9 @@ fn draw_ui(
         Screen::Calibrate(form) => {
             draw_calibrate(frame, right_panels[0], data, form, hitboxes);
         }
+        Screen::Preset(form) => {
+            draw_preset(frame, right_panels[0], data, form, hitboxes);
+        }
         Screen::AdjustSelect { selected } => {
             draw_target_list(
                 frame,
@@ -1216,13 +1513,14 @@ fn draw_ui(
     draw_logs(frame, right_panels[1], logs);
 
     let help = match screen {
-        Screen::Dashboard => "  ↑/↓ select  Enter run  PgUp/PgDn scroll  q quit",
+        Screen::Dashboard => "  ↑/↓ select  Enter run  PgUp/PgDn scroll  u undo  q quit",
         Screen::Calibrate(_) => "  ↑/↓ field  ←/→ change  type steps  Enter activate  Esc back",
+        Screen::Preset(_) => "  ↑/↓ field  ←/→ change  Enter activate  Esc back",
         Screen::AdjustSelect { .. } | Screen::Recenter { .. } | Screen::Reset { .. } => {
             "  ↑/↓ select  Enter confirm  Esc back"
         }
         Screen::AdjustEdit { .. } => {
-            "  ↑/↓ axis  ←/→ button  Enter activate  +/- apply sign  1..4 magnitude  Esc targets"
+            "  ↑/↓ axis  ←/→ button  Enter activate  +/- apply sign  1..4 magnitude  u undo  Esc targets"
         }
     };
     let mut footer = Vec::new();
@@ -1318,7 +1616,7 @@ fn overview_lines(data: &CalibratorData<'_>) -> Vec<Line<'static>> {
         .iter()
         .map(|origin| origin.id)
         .collect::<Vec<_>>();
-    for device in &data.devices {
+    for (_, device) in data.connected_devices() {
         if !origin_ids.contains(&device.tracking_origin) {
             origin_ids.push(device.tracking_origin);
         }
@@ -1382,7 +1680,7 @@ fn overview_lines(data: &CalibratorData<'_>) -> Vec<Line<'static>> {
         let devices = data
             .devices
             .iter()
-            .filter(|device| device.tracking_origin == origin_id)
+            .filter(|device| device.connected && device.tracking_origin == origin_id)
             .collect::<Vec<_>>();
         for (device_index, device) in devices.iter().enumerate() {
             let last_device = device_index + 1 == devices.len();
@@ -1442,7 +1740,7 @@ fn overview_lines(data: &CalibratorData<'_>) -> Vec<Line<'static>> {
         }
     }
 
-    if data.devices.is_empty() {
+    if data.connected_devices().next().is_none() {
         lines.push(Line::from(Span::styled(
             "  No devices",
             Style::default().fg(Color::DarkGray),
@@ -1682,14 +1980,126 @@ fn draw_calibrate(
         action: MouseAction::CalibrateToggle,
     });
 
+    let half = inner.width / 2;
     draw_button(
         frame,
-        bounded_rect(inner, 0, 12, inner.width, 3),
+        bounded_rect(inner, 0, 12, half, 3),
         "Start",
         form.selected == 4,
         MouseAction::CalibrateStart,
         hitboxes,
     );
+    draw_button(
+        frame,
+        bounded_rect(inner, half, 12, inner.width - half, 3),
+        "Detect",
+        form.selected == 5,
+        MouseAction::CalibrateDetect,
+        hitboxes,
+    );
+}
+
+fn draw_preset(
+    frame: &mut Frame<'_>,
+    area: Rect,
+    data: &CalibratorData<'_>,
+    form: &PresetForm,
+    hitboxes: &mut Vec<Hitbox>,
+) {
+    let block = Block::default()
+        .title(" Presets ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Blue));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let (name, description) = match form.presets.get(form.preset) {
+        Some(preset) => (preset.name.as_str(), preset.description.as_str()),
+        None => ("None", ""),
+    };
+    frame.render_widget(
+        Paragraph::new(description)
+            .style(Style::default().fg(Color::Gray))
+            .wrap(ratatui::widgets::Wrap { trim: true }),
+        bounded_rect(inner, 0, 0, inner.width, 2),
+    );
+
+    draw_selector(
+        frame,
+        bounded_rect(inner, 0, 3, inner.width, 1),
+        "Preset",
+        name,
+        form.selected == 0,
+        MouseAction::PresetCycle(-1),
+        MouseAction::PresetCycle(1),
+        hitboxes,
+    );
+    let source = device_label(data, form.source);
+    let target = device_label(data, form.target);
+    draw_selector(
+        frame,
+        bounded_rect(inner, 0, 5, inner.width, 1),
+        "Source",
+        &source,
+        form.selected == 1,
+        MouseAction::PresetCycleDevice {
+            source: true,
+            delta: -1,
+        },
+        MouseAction::PresetCycleDevice {
+            source: true,
+            delta: 1,
+        },
+        hitboxes,
+    );
+    draw_selector(
+        frame,
+        bounded_rect(inner, 0, 7, inner.width, 1),
+        "Target",
+        &target,
+        form.selected == 2,
+        MouseAction::PresetCycleDevice {
+            source: false,
+            delta: -1,
+        },
+        MouseAction::PresetCycleDevice {
+            source: false,
+            delta: 1,
+        },
+        hitboxes,
+    );
+    draw_selector(
+        frame,
+        bounded_rect(inner, 0, 9, inner.width, 1),
+        "Profile",
+        &form.profiles[form.profile],
+        form.selected == 3,
+        MouseAction::PresetCycleProfile(-1),
+        MouseAction::PresetCycleProfile(1),
+        hitboxes,
+    );
+    let checkbox = bounded_rect(inner, 0, 11, inner.width, 1);
+    frame.render_widget(
+        Paragraph::new(format!(
+            "{} Refine by moving the headset around",
+            if form.refine { "[x]" } else { "[ ]" }
+        ))
+        .style(field_style(form.selected == 4)),
+        checkbox,
+    );
+    hitboxes.push(Hitbox {
+        rect: checkbox,
+        action: MouseAction::PresetToggle,
+    });
+
+    draw_button(
+        frame,
+        bounded_rect(inner, 0, 13, inner.width, 3),
+        "Start",
+        form.selected == 5,
+        MouseAction::PresetStart,
+        hitboxes,
+    );
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -1934,10 +2344,15 @@ fn device_label(data: &CalibratorData<'_>, index: usize) -> String {
     let Some(device) = data.devices.get(index) else {
         return "Unavailable".into();
     };
-    if device.inner.name.is_empty() || device.inner.name == device.serial {
+    let label = if device.inner.name.is_empty() || device.inner.name == device.serial {
         format!("[{}] {}", device.index, device.serial)
     } else {
         format!("[{}] {}", device.index, device.inner.name)
+    };
+    if device.connected {
+        label
+    } else {
+        format!("{label} (disconnected)")
     }
 }
 
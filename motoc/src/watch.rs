@@ -0,0 +1,130 @@
+use std::time::{Duration, Instant};
+
+use libmotoc::{Calibrator, CalibratorData, NameMatcher};
+
+use super::continue_from_profile;
+
+// how often the profile is tried again while something is missing
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Serves `motoc watch`: applies a profile once its devices are present and
+/// tracking, and again whenever devices come or go.
+pub struct Watch {
+    config: libmotoc::Config,
+    // without one, the best fitting profile is picked on every change
+    profile: Option<String>,
+    matcher: NameMatcher,
+    last_try: Option<Instant>,
+    // whether the profile was applied since the devices last changed
+    applied: bool,
+    // why the profile is not applied yet, logged when it changes
+    waiting: Option<String>,
+    // the profile the running calibrator was started from
+    following: Option<String>,
+}
+
+impl Watch {
+    pub fn new(config: libmotoc::Config, profile: Option<String>, fuzzy: bool) -> Self {
+        Self {
+            config,
+            profile,
+            matcher: NameMatcher::load(fuzzy),
+            last_try: None,
+            applied: false,
+            waiting: None,
+            following: None,
+        }
+    }
+
+    /// Devices came or went, see `CalibratorData::refresh`.
+    pub fn devices_changed(&mut self) {
+        self.applied = false;
+        self.last_try = None;
+    }
+
+    /// The calibrator the profile started failed, so the profile is applied again.
+    pub fn calibrator_failed(&mut self) {
+        self.applied = false;
+        self.following = None;
+    }
+
+    /// Returns the name of the profile if it was applied just now.
+    pub fn step(
+        &mut self,
+        data: &mut CalibratorData,
+        calibrator: &mut Option<Box<dyn Calibrator>>,
+    ) -> anyhow::Result<Option<String>> {
+        if self.applied || self.last_try.is_some_and(|t| t.elapsed() < RETRY_INTERVAL) {
+            return Ok(None);
+        }
+        self.last_try = Some(Instant::now());
+
+        match self.try_apply(data, calibrator)? {
+            Ok(applied) => {
+                self.applied = true;
+                self.waiting = None;
+                Ok(applied)
+            }
+            Err(reason) => {
+                if self.waiting.as_ref() != Some(&reason) {
+                    log::info!("Waiting: {}", reason);
+                    self.waiting = Some(reason);
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Applies the profile if everything it needs is there, and returns its name
+    /// unless it was already running. Otherwise, returns what is missing or
+    /// what went wrong, e.g. another motoc process holding an offset.
+    fn try_apply(
+        &mut self,
+        data: &mut CalibratorData,
+        calibrator: &mut Option<Box<dyn Calibrator>>,
+    ) -> anyhow::Result<Result<Option<String>, String>> {
+        let (name, profile) = match self.profile.as_ref() {
+            Some(name) => match data.load_calibration(name) {
+                Ok(profile) => (name.clone(), profile),
+                Err(e) => return Ok(Err(format!("cannot load {}: {}", name, e))),
+            },
+            None => match data.select_profile()? {
+                Some(choice) => (choice.name, choice.profile),
+                None => return Ok(Err(String::from("no saved profile fits the devices"))),
+            },
+        };
+
+        self.matcher.reload();
+        let missing = data.missing_for(&profile, &self.matcher);
+        if !missing.is_empty() {
+            return Ok(Err(format!("{} needs {}", name, missing.join(", "))));
+        }
+
+        // continuous mode pauses and resumes by itself, starting over would lose its progress
+        if calibrator.is_some() && self.following.as_ref() == Some(&name) {
+            log::debug!("Profile '{}' is still running.", name);
+            return Ok(Ok(None));
+        }
+
+        log::info!("Applying profile '{}'.", name);
+        if let Some(mut cal) = calibrator.take() {
+            cal.finish(data)?;
+        }
+        self.following = None;
+        data.tuning = self.config.tuning(Some(&name))?;
+        let method = continue_from_profile(data, &self.config, Some(&name), self.matcher.fuzzy)
+            .and_then(|method| match method {
+                Some(mut method) => method.init(data).map(|_| Some(method)).map_err(Into::into),
+                None => Ok(None),
+            });
+        match method {
+            Ok(Some(method)) => {
+                *calibrator = Some(method);
+                self.following = Some(name.clone());
+            }
+            Ok(None) => {}
+            Err(e) => return Ok(Err(format!("cannot apply {}: {:#}", name, e))),
+        }
+        Ok(Ok(Some(name)))
+    }
+}